@@ -0,0 +1,306 @@
+use std::{f64::consts::PI, fmt::Display};
+
+use crate::{matrix::Matrix, point::Point};
+
+/// Bearings from each anchor towards the tag, in radians counter-clockwise from the x axis
+#[derive(PartialEq, Debug, Clone)]
+pub struct AoaMeasurement {
+    pub bearings: Vec<f64>,
+}
+
+impl AoaMeasurement {
+    pub fn new(bearings: Vec<f64>) -> Self {
+        AoaMeasurement { bearings }
+    }
+
+    /// Returns the bearings a tag at `pt` would produce
+    pub fn predict(anchors: &[Point], pt: &Point) -> Vec<f64> {
+        anchors.iter().map(|anchor| bearing(anchor, pt)).collect()
+    }
+}
+
+impl Display for AoaMeasurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bearings_str = self
+            .bearings
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        write!(f, "{}", bearings_str)
+    }
+}
+
+/// Solver of bearings, `None` when it finds no fix
+pub type AoaCallback = fn(&[Point], &AoaMeasurement) -> Option<Point>;
+/// Solver of ranges and bearings together, `None` when it finds no fix
+pub type HybridCallback = fn(&[Point], &[f64], &AoaMeasurement) -> Option<Point>;
+
+/// Bearing of `to` as seen from `from`
+pub fn bearing(from: &Point, to: &Point) -> f64 {
+    let delta = to - from;
+
+    delta.y.atan2(delta.x)
+}
+
+/// Wraps an angle into `[-PI, PI)`
+pub fn wrap_angle(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// Least squares intersection of the bearing lines, needs at least 2 non parallel bearings
+pub fn triangulate(anchors: &[Point], measurement: &AoaMeasurement) -> Option<Point> {
+    let n = anchors.len();
+
+    if n < 2 || measurement.bearings.len() != n {
+        return None;
+    }
+
+    // Every bearing line satisfies `normal . pt = normal . anchor`
+    let mut a = Matrix::new(n, 2);
+    let mut b = Matrix::new(n, 1);
+
+    for (i, (anchor, angle)) in anchors.iter().zip(&measurement.bearings).enumerate() {
+        let normal = Point::new(-angle.sin(), angle.cos());
+
+        a.set(i, 0, normal.x)?;
+        a.set(i, 1, normal.y)?;
+        b.set(i, 0, (&normal * anchor).sum())?;
+    }
+
+    let a_t = a.transpose();
+    let result = a_t.dot(&a)?.invert()?.dot(&a_t)?.dot(&b)?;
+
+    Some(Point::new(*result.get(0, 0)?, *result.get(1, 0)?))
+}
+
+/// Sum of squared range and arc length residuals used by [`hybrid`]
+fn hybrid_cost(
+    anchors: &[Point],
+    distances: &[f64],
+    measurement: &AoaMeasurement,
+    pt: &Point,
+) -> f64 {
+    anchors
+        .iter()
+        .zip(distances)
+        .zip(&measurement.bearings)
+        .map(|((anchor, dist), angle)| {
+            (dist - anchor.distance_to(pt)).powi(2)
+                + (dist * wrap_angle(angle - bearing(anchor, pt))).powi(2)
+        })
+        .sum()
+}
+
+/// Gauss-Newton fit of ranges and bearings together.
+///
+/// Bearing errors are scaled by the measured range into arc lengths, so both kinds of residual are
+/// distances and no relative weighting has to be supplied. Steps that would increase the cost are
+/// halved, since bearings get very non linear close to an anchor.
+pub fn hybrid(
+    anchors: &[Point],
+    distances: &[f64],
+    measurement: &AoaMeasurement,
+    initial: &Point,
+    max_iterations: usize,
+) -> Option<Point> {
+    let n = anchors.len();
+
+    if n < 1 || distances.len() != n || measurement.bearings.len() != n {
+        return None;
+    }
+
+    let mut pt = initial.clone();
+
+    for _ in 0..max_iterations {
+        let mut jacobian = Matrix::new(2 * n, 2);
+        let mut residuals = Matrix::new(2 * n, 1);
+
+        for i in 0..n {
+            let delta = &pt - &anchors[i];
+            let dist = delta.pow(2).sum().sqrt();
+            let arc = distances[i] / dist.powi(2);
+
+            jacobian.set(2 * i, 0, delta.x / dist)?;
+            jacobian.set(2 * i, 1, delta.y / dist)?;
+            residuals.set(2 * i, 0, distances[i] - dist)?;
+
+            jacobian.set(2 * i + 1, 0, -delta.y * arc)?;
+            jacobian.set(2 * i + 1, 1, delta.x * arc)?;
+            residuals.set(
+                2 * i + 1,
+                0,
+                distances[i] * wrap_angle(measurement.bearings[i] - bearing(&anchors[i], &pt)),
+            )?;
+        }
+
+        let jacobian_t = jacobian.transpose();
+        let step = jacobian_t
+            .dot(&jacobian)?
+            .invert()?
+            .dot(&jacobian_t)?
+            .dot(&residuals)?;
+        let mut step = Point::new(*step.get(0, 0)?, *step.get(1, 0)?);
+
+        let cost = hybrid_cost(anchors, distances, measurement, &pt);
+        let mut next = &pt + &step;
+
+        for _ in 0..10 {
+            if hybrid_cost(anchors, distances, measurement, &next) <= cost {
+                break;
+            }
+
+            step = &step / 2.0;
+            next = &pt + &step;
+        }
+
+        pt = next;
+
+        if !(pt.x.is_finite() && pt.y.is_finite()) {
+            return None;
+        }
+
+        if step.pow(2).sum().sqrt() < 1e-9 {
+            break;
+        }
+    }
+
+    Some(pt)
+}
+
+/// Triangulation from bearings alone
+pub fn bearing_way(anchors: &[Point], measurement: &AoaMeasurement) -> Option<Point> {
+    triangulate(anchors, measurement)
+}
+
+/// Averages the fixes each anchor gets on its own by following its bearing for its range, needs
+/// at least one anchor
+pub fn polar_fix(
+    anchors: &[Point],
+    distances: &[f64],
+    measurement: &AoaMeasurement,
+) -> Option<Point> {
+    let n = anchors.len();
+
+    if n < 1 || distances.len() != n || measurement.bearings.len() != n {
+        return None;
+    }
+
+    let sum = anchors
+        .iter()
+        .zip(distances)
+        .zip(&measurement.bearings)
+        .map(|((anchor, dist), angle)| anchor + &Point::new(dist * angle.cos(), dist * angle.sin()))
+        .fold(Point::new(0.0, 0.0), |sum, pt| &sum + &pt);
+
+    Some(&sum / n as f64)
+}
+
+/// Hybrid range and bearing fit, seeded by [`polar_fix`] which unlike triangulation does not
+/// break down when the bearing lines are close to parallel
+pub fn hybrid_way(
+    anchors: &[Point],
+    distances: &[f64],
+    measurement: &AoaMeasurement,
+) -> Option<Point> {
+    let initial = polar_fix(anchors, distances, measurement)?;
+
+    hybrid(anchors, distances, measurement, &initial, 10).or(Some(initial))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Option<Point>, expected: &Point) {
+        let actual = actual.expect("Bad missing fix");
+
+        assert!(
+            actual.distance_to(expected) < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn anchors() -> Vec<Point> {
+        vec![
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(0.0, 100.0),
+        ]
+    }
+
+    fn exact(tag: &Point) -> (Vec<f64>, AoaMeasurement) {
+        let anchors = anchors();
+
+        (
+            anchors
+                .iter()
+                .map(|anchor| anchor.distance_to(tag))
+                .collect(),
+            AoaMeasurement::new(AoaMeasurement::predict(&anchors, tag)),
+        )
+    }
+
+    #[test]
+    fn triangulate_recovers_the_tag() {
+        let tag = Point::new(30.0, 40.0);
+        let (_, measurement) = exact(&tag);
+
+        assert_near(triangulate(&anchors(), &measurement), &tag);
+        assert_near(
+            bearing_way(
+                &anchors()[..2],
+                &AoaMeasurement::new(measurement.bearings[..2].to_vec()),
+            ),
+            &tag,
+        );
+    }
+
+    #[test]
+    fn triangulate_rejects_parallel_bearings() {
+        let anchors = [Point::new(0.0, 0.0), Point::new(10.0, 0.0)];
+        let measurement = AoaMeasurement::new(vec![0.0, 0.0]);
+
+        assert_eq!(triangulate(&anchors, &measurement), None);
+        assert_eq!(triangulate(&anchors[..1], &measurement), None);
+    }
+
+    #[test]
+    fn polar_fix_recovers_the_tag() {
+        let tag = Point::new(70.0, 20.0);
+        let (distances, measurement) = exact(&tag);
+
+        assert_near(polar_fix(&anchors(), &distances, &measurement), &tag);
+        assert_eq!(polar_fix(&[], &[], &AoaMeasurement::new(vec![])), None);
+    }
+
+    #[test]
+    fn hybrid_recovers_the_tag() {
+        let tag = Point::new(55.0, 65.0);
+        let (distances, measurement) = exact(&tag);
+
+        assert_near(
+            hybrid(
+                &anchors(),
+                &distances,
+                &measurement,
+                &Point::new(50.0, 50.0),
+                10,
+            ),
+            &tag,
+        );
+        assert_near(hybrid_way(&anchors(), &distances, &measurement), &tag);
+    }
+
+    #[test]
+    fn hybrid_works_from_one_anchor() {
+        let tag = Point::new(30.0, 40.0);
+        let (distances, measurement) = exact(&tag);
+        let single = AoaMeasurement::new(measurement.bearings[..1].to_vec());
+
+        assert_near(hybrid_way(&anchors()[..1], &distances[..1], &single), &tag);
+    }
+}
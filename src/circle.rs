@@ -40,7 +40,7 @@ impl Circle {
         }
     }
 
-    pub fn scale(&mut self, factor: f64) -> () {
+    pub fn scale(&mut self, factor: f64) {
         self.radius *= factor;
     }
 
@@ -206,6 +206,22 @@ impl TwoDimShape for Circle {
     fn calc_max_span(&self) -> f64 {
         self.calc_diameter()
     }
+
+    fn calc_bounding_box(&self) -> Rectangle {
+        Rectangle::new(
+            self.pt.x - self.radius,
+            self.pt.y - self.radius,
+            self.calc_diameter(),
+            self.calc_diameter(),
+        )
+    }
+
+    fn to_svg(&self) -> String {
+        format!(
+            r#"<circle cx="{}" cy="{}" r="{}"/>"#,
+            self.pt.x, self.pt.y, self.radius
+        )
+    }
 }
 
 impl Contains<Point> for Circle {
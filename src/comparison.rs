@@ -0,0 +1,269 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::{
+    report::{
+        format_percent, format_value, markdown_table, ExperimentConfig, ReportFormat, Summary,
+    },
+    stats,
+};
+
+/// Two sided 95% quantile of the standard normal distribution
+const Z_95: f64 = 1.959964;
+
+/// Errors of several localizers on the same trials
+#[derive(PartialEq, Debug, Clone)]
+pub struct Comparison {
+    pub names: Vec<String>,
+    /// `deltas[localizer][trial]`
+    pub deltas: Vec<Vec<f64>>,
+}
+
+impl Comparison {
+    /// From the deltas of every localizer, trial by trial
+    pub fn new(names: Vec<String>, trials: Vec<Vec<f64>>) -> Self {
+        let deltas = (0..names.len())
+            .map(|localizer| trials.iter().map(|trial| trial[localizer]).collect())
+            .collect();
+
+        Comparison { names, deltas }
+    }
+
+    pub fn trials(&self) -> usize {
+        self.deltas.first().map_or(0, Vec::len)
+    }
+
+    /// Fraction of the trials every localizer had the lowest error in, ties counting for each of
+    /// the tied localizers. Trials without a single fix are left out.
+    pub fn win_rates(&self) -> Vec<f64> {
+        let mut wins = vec![0; self.names.len()];
+        let mut decided = 0;
+
+        for trial in 0..self.trials() {
+            let best = self
+                .deltas
+                .iter()
+                .map(|deltas| deltas[trial])
+                .filter(|delta| !delta.is_nan())
+                .min_by(|a, b| a.total_cmp(b));
+
+            if let Some(best) = best {
+                decided += 1;
+
+                for (localizer, deltas) in self.deltas.iter().enumerate() {
+                    if deltas[trial] == best {
+                        wins[localizer] += 1;
+                    }
+                }
+            }
+        }
+
+        wins.iter()
+            .map(|wins| *wins as f64 / decided.max(1) as f64)
+            .collect()
+    }
+
+    /// Paired statistics of localizer `first` against `second`
+    pub fn paired(&self, first: usize, second: usize) -> PairedStats {
+        let (a, b) = (&self.deltas[first], &self.deltas[second]);
+        let differences = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| a - b)
+            .filter(|difference| !difference.is_nan())
+            .collect::<Vec<_>>();
+
+        let count = differences.len();
+        let std = stats::std(&differences);
+
+        PairedStats {
+            first: self.names[first].clone(),
+            second: self.names[second].clone(),
+            trials: count,
+            mean_difference: stats::mean(&differences),
+            confidence_interval: std.map(|std| Z_95 * std / (count as f64).sqrt()),
+            median_difference: stats::median(&differences),
+            first_better: differences.iter().filter(|d| **d < 0.0).count() as f64
+                / count.max(1) as f64,
+            second_better: differences.iter().filter(|d| **d > 0.0).count() as f64
+                / count.max(1) as f64,
+        }
+    }
+}
+
+/// How one localizer did on the shared trials
+#[derive(PartialEq, Debug, Clone, Serialize)]
+pub struct SolverStats {
+    pub name: String,
+    pub summary: Summary,
+    /// See [`Comparison::win_rates`]
+    pub win_rate: f64,
+}
+
+/// Differences `first - second` between the errors of two localizers, over the trials both got
+/// a fix in. Negative differences favour `first`.
+#[derive(PartialEq, Debug, Clone, Serialize)]
+pub struct PairedStats {
+    pub first: String,
+    pub second: String,
+    pub trials: usize,
+    pub mean_difference: Option<f64>,
+    /// Half width of the 95% confidence interval of `mean_difference`
+    pub confidence_interval: Option<f64>,
+    pub median_difference: Option<f64>,
+    /// Fraction of the trials `first` had the lower error in
+    pub first_better: f64,
+    pub second_better: f64,
+}
+
+/// Configuration and paired results of a [`Comparison`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ComparisonReport {
+    pub config: ExperimentConfig,
+    pub solvers: Vec<SolverStats>,
+    /// Every pair of localizers, in the order they were given
+    pub pairs: Vec<PairedStats>,
+}
+
+impl ComparisonReport {
+    pub fn new(config: ExperimentConfig, comparison: &Comparison) -> Self {
+        let n = comparison.names.len();
+
+        ComparisonReport {
+            config,
+            solvers: comparison
+                .names
+                .iter()
+                .zip(&comparison.deltas)
+                .zip(comparison.win_rates())
+                .map(|((name, deltas), win_rate)| SolverStats {
+                    name: name.clone(),
+                    summary: Summary::new(deltas),
+                    win_rate,
+                })
+                .collect(),
+            pairs: (0..n)
+                .flat_map(|first| (first + 1..n).map(move |second| (first, second)))
+                .map(|(first, second)| comparison.paired(first, second))
+                .collect(),
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_string(),
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Bad report serialization")
+    }
+
+    /// Configuration, localizer and pair tables
+    pub fn to_markdown(&self) -> String {
+        let solvers = self
+            .solvers
+            .iter()
+            .map(|solver| {
+                vec![
+                    solver.name.clone(),
+                    format_value(solver.summary.mean),
+                    format_value(solver.summary.median),
+                    format_value(solver.summary.rmse),
+                    format_value(solver.summary.cep95),
+                    solver.summary.failed.to_string(),
+                    format_percent(solver.win_rate),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|pair| {
+                vec![
+                    format!("{} - {}", pair.first, pair.second),
+                    format_value(pair.mean_difference),
+                    format_value(pair.confidence_interval),
+                    format_value(pair.median_difference),
+                    format_percent(pair.first_better),
+                    format_percent(pair.second_better),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        [
+            "## Configuration".to_string(),
+            self.config.to_markdown(),
+            "## Localizers".to_string(),
+            markdown_table(
+                &[
+                    "Localizer",
+                    "Mean",
+                    "Median",
+                    "RMSE",
+                    "CEP95",
+                    "Failed",
+                    "Win Rate",
+                ],
+                &solvers,
+            ),
+            "## Paired Differences".to_string(),
+            markdown_table(
+                &[
+                    "Pair",
+                    "Mean",
+                    "95% CI",
+                    "Median",
+                    "First Better",
+                    "Second Better",
+                ],
+                &pairs,
+            ),
+        ]
+        .join("\n\n")
+    }
+}
+
+impl Display for ComparisonReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.config)?;
+        writeln!(f)?;
+
+        for solver in &self.solvers {
+            writeln!(
+                f,
+                "{}: Mean: {}, Median: {}, RMSE: {}, CEP95: {}, Failed: {}, Win Rate: {}",
+                solver.name,
+                format_value(solver.summary.mean),
+                format_value(solver.summary.median),
+                format_value(solver.summary.rmse),
+                format_value(solver.summary.cep95),
+                solver.summary.failed,
+                format_percent(solver.win_rate)
+            )?;
+        }
+        writeln!(f)?;
+
+        for pair in &self.pairs {
+            writeln!(
+                f,
+                "{} - {}: Mean: {} (± {}), Median: {}, {} Better: {}, {} Better: {}",
+                pair.first,
+                pair.second,
+                format_value(pair.mean_difference),
+                format_value(pair.confidence_interval),
+                format_value(pair.median_difference),
+                pair.first,
+                format_percent(pair.first_better),
+                pair.second,
+                format_percent(pair.second_better)
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{matrix::Matrix, point::Point, rectangle::Rectangle, stats};
+
+/// Localization error per cell of a grid over the bounds.
+///
+/// Row `0` of every matrix is the row of cells along the bottom edge of the bounds and column `0`
+/// the one along the left edge.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    pub bounds: Rectangle,
+    pub mean: Matrix,
+    pub median: Matrix,
+    pub p95: Matrix,
+}
+
+impl Heatmap {
+    /// Summarises `deltas` holding the same number of trials for every cell, cell by cell in row
+    /// order
+    pub fn new(bounds: Rectangle, cols: usize, rows: usize, deltas: &[f64]) -> Self {
+        let trials = deltas.len() / (cols * rows).max(1);
+        let (mut mean, mut median, mut p95) = (
+            Matrix::new(rows, cols),
+            Matrix::new(rows, cols),
+            Matrix::new(rows, cols),
+        );
+
+        for cell in 0..rows * cols {
+            let (row, col) = (cell / cols, cell % cols);
+            let values = &deltas[cell * trials..(cell + 1) * trials];
+            let [cell_median, cell_p95] =
+                stats::quantiles(values, &[0.5, 0.95]).map_or([f64::NAN; 2], |q| [q[0], q[1]]);
+
+            mean.set(row, col, stats::mean(values).unwrap_or(f64::NAN));
+            median.set(row, col, cell_median);
+            p95.set(row, col, cell_p95);
+        }
+
+        Heatmap {
+            bounds,
+            mean,
+            median,
+            p95,
+        }
+    }
+
+    /// Centre of the cell at `row`, `col` of a `cols x rows` grid over `bounds`
+    pub fn cell_center(
+        bounds: &Rectangle,
+        cols: usize,
+        rows: usize,
+        row: usize,
+        col: usize,
+    ) -> Point {
+        Point::new(
+            bounds.pt.x + (col as f64 + 0.5) * bounds.width / cols as f64,
+            bounds.pt.y + (row as f64 + 0.5) * bounds.height / rows as f64,
+        )
+    }
+
+    /// One line per cell with its centre and error statistics, under a header line
+    pub fn to_csv(&self) -> String {
+        let (rows, cols) = (self.mean.n_rows, self.mean.n_cols);
+
+        let lines = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                let center = Heatmap::cell_center(&self.bounds, cols, rows, row, col);
+
+                format!(
+                    "{},{},{},{},{}",
+                    center.x,
+                    center.y,
+                    self.mean.get(row, col).unwrap(),
+                    self.median.get(row, col).unwrap(),
+                    self.p95.get(row, col).unwrap()
+                )
+            });
+
+        std::iter::once("x,y,mean,median,p95".to_string())
+            .chain(lines)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{multilateration::paper_way, placement::AnchorPlacement, test_runner::TestRunner};
+
+    use super::*;
+
+    #[test]
+    fn cells_summarise_their_own_trials() {
+        let heatmap = Heatmap::new(
+            Rectangle::new(0.0, 0.0, 100.0, 50.0),
+            2,
+            1,
+            &[1.0, 2.0, 3.0, 10.0, f64::NAN, 30.0],
+        );
+
+        assert_eq!(heatmap.mean.data, vec![2.0, 20.0]);
+        assert_eq!(heatmap.median.data, vec![2.0, 20.0]);
+        assert_eq!(
+            heatmap.to_csv().lines().collect::<Vec<_>>(),
+            vec!["x,y,mean,median,p95", "25,25,2,2,2.9", "75,25,20,20,29",]
+        );
+    }
+
+    #[test]
+    fn exact_ranges_map_to_no_error() {
+        let mut runner = TestRunner::new(4, 0.0, Rectangle::new(0.0, 0.0, 100.0, 100.0), paper_way);
+        runner.placement = AnchorPlacement::Corners;
+
+        let heatmap = runner.run_heatmap(3, 2, 4);
+
+        assert_eq!((heatmap.mean.n_rows, heatmap.mean.n_cols), (2, 3));
+        assert!(heatmap.mean.data.iter().all(|delta| *delta < 1e-6));
+    }
+}
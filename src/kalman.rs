@@ -0,0 +1,389 @@
+use crate::{matrix::Matrix, point::Point, uncertainty};
+
+/// Sequential position estimator fed with range measurements over time
+pub trait Tracker {
+    /// Propagates the state `dt` seconds forward
+    fn predict(&mut self, dt: f64);
+    /// Corrects the state with `distances` to `anchors`, measured with the given variances
+    fn update(&mut self, anchors: &[Point], distances: &[f64], variances: &[f64]);
+
+    fn position(&self) -> Point;
+    fn velocity(&self) -> Point;
+    /// 2x2 covariance of `position`
+    fn position_covariance(&self) -> Matrix;
+}
+
+/// Shared `[x, y, vx, vy]` state of the constant velocity filters
+#[derive(PartialEq, Debug, Clone)]
+struct ConstantVelocityState {
+    state: Matrix,
+    covariance: Matrix,
+    /// Spectral density of the white noise acceleration driving the velocity
+    process_noise: f64,
+}
+
+impl ConstantVelocityState {
+    fn new(initial: &Point, position_std: f64, velocity_std: f64, process_noise: f64) -> Self {
+        ConstantVelocityState {
+            state: Matrix::from_iter(4, 1, [initial.x, initial.y, 0.0, 0.0]),
+            covariance: Matrix::from_diagonal(&[
+                position_std.powi(2),
+                position_std.powi(2),
+                velocity_std.powi(2),
+                velocity_std.powi(2),
+            ]),
+            process_noise,
+        }
+    }
+
+    fn predict(&mut self, dt: f64) {
+        let transition = Matrix::from_iter(
+            4,
+            4,
+            [
+                1.0, 0.0, dt, 0.0, //
+                0.0, 1.0, 0.0, dt, //
+                0.0, 0.0, 1.0, 0.0, //
+                0.0, 0.0, 0.0, 1.0,
+            ],
+        );
+
+        let (dt2, dt3) = (dt.powi(2) / 2.0, dt.powi(3) / 3.0);
+        let noise = &Matrix::from_iter(
+            4,
+            4,
+            [
+                dt3, 0.0, dt2, 0.0, //
+                0.0, dt3, 0.0, dt2, //
+                dt2, 0.0, dt, 0.0, //
+                0.0, dt2, 0.0, dt,
+            ],
+        ) * self.process_noise;
+
+        self.state = transition.dot(&self.state).unwrap();
+        self.covariance = (&transition
+            .dot(&self.covariance)
+            .unwrap()
+            .dot(&transition.transpose())
+            .unwrap()
+            + &noise)
+            .unwrap();
+    }
+
+    /// Applies the Kalman `gain` to the measurement `innovation`
+    fn correct(&mut self, gain: &Matrix, innovation: &Matrix, innovation_cov: &Matrix) {
+        self.state = (&self.state + &gain.dot(innovation).unwrap()).unwrap();
+        self.covariance = (&self.covariance
+            - &gain
+                .dot(innovation_cov)
+                .unwrap()
+                .dot(&gain.transpose())
+                .unwrap())
+            .unwrap();
+    }
+
+    fn position(&self) -> Point {
+        Point::new(self.state.data[0], self.state.data[1])
+    }
+
+    fn velocity(&self) -> Point {
+        Point::new(self.state.data[2], self.state.data[3])
+    }
+
+    fn position_covariance(&self) -> Matrix {
+        Matrix::from_iter(
+            2,
+            2,
+            [
+                self.covariance.data[0],
+                self.covariance.data[1],
+                self.covariance.data[4],
+                self.covariance.data[5],
+            ],
+        )
+    }
+}
+
+/// Constant velocity Extended Kalman Filter, linearising the ranges around the predicted position
+#[derive(PartialEq, Debug, Clone)]
+pub struct ExtendedKalmanFilter {
+    inner: ConstantVelocityState,
+}
+
+impl ExtendedKalmanFilter {
+    pub fn new(initial: &Point, position_std: f64, velocity_std: f64, process_noise: f64) -> Self {
+        ExtendedKalmanFilter {
+            inner: ConstantVelocityState::new(initial, position_std, velocity_std, process_noise),
+        }
+    }
+}
+
+impl Tracker for ExtendedKalmanFilter {
+    fn predict(&mut self, dt: f64) {
+        self.inner.predict(dt);
+    }
+
+    fn update(&mut self, anchors: &[Point], distances: &[f64], variances: &[f64]) {
+        let n = anchors.len();
+        let position = self.inner.position();
+
+        let ranges = uncertainty::range_jacobian(anchors, &position);
+        let jacobian = Matrix::from_iter(
+            n,
+            4,
+            (0..n).flat_map(|i| {
+                [
+                    *ranges.get(i, 0).unwrap(),
+                    *ranges.get(i, 1).unwrap(),
+                    0.0,
+                    0.0,
+                ]
+            }),
+        );
+        let innovation = Matrix::from_iter(
+            n,
+            1,
+            anchors
+                .iter()
+                .zip(distances)
+                .map(|(anchor, dist)| dist - anchor.distance_to(&position)),
+        );
+
+        let cross_cov = self.inner.covariance.dot(&jacobian.transpose()).unwrap();
+        let innovation_cov =
+            (&jacobian.dot(&cross_cov).unwrap() + &Matrix::from_diagonal(variances)).unwrap();
+
+        if let Some(inverse) = innovation_cov.invert() {
+            let gain = cross_cov.dot(&inverse).unwrap();
+
+            self.inner.correct(&gain, &innovation, &innovation_cov);
+        }
+    }
+
+    fn position(&self) -> Point {
+        self.inner.position()
+    }
+
+    fn velocity(&self) -> Point {
+        self.inner.velocity()
+    }
+
+    fn position_covariance(&self) -> Matrix {
+        self.inner.position_covariance()
+    }
+}
+
+/// Constant velocity Unscented Kalman Filter, pushing sigma points through the range equations.
+///
+/// The motion model is linear, so only the measurement update uses the unscented transform.
+#[derive(PartialEq, Debug, Clone)]
+pub struct UnscentedKalmanFilter {
+    inner: ConstantVelocityState,
+    pub alpha: f64,
+    pub beta: f64,
+    pub kappa: f64,
+}
+
+impl UnscentedKalmanFilter {
+    pub fn new(initial: &Point, position_std: f64, velocity_std: f64, process_noise: f64) -> Self {
+        UnscentedKalmanFilter {
+            inner: ConstantVelocityState::new(initial, position_std, velocity_std, process_noise),
+            alpha: 1.0,
+            beta: 2.0,
+            kappa: 0.0,
+        }
+    }
+
+    /// Sigma points as the columns of a `4 x 9` matrix, with their mean and covariance weights
+    fn sigma_points(&self) -> Option<(Matrix, Vec<f64>, Vec<f64>)> {
+        let n = 4.0;
+        let lambda = self.alpha.powi(2) * (n + self.kappa) - n;
+        let root = (&self.inner.covariance * (n + lambda)).cholesky()?;
+
+        let mut points = Matrix::new(4, 9);
+
+        for row in 0..4 {
+            let mean = self.inner.state.data[row];
+
+            points.set(row, 0, mean)?;
+
+            for col in 0..4 {
+                let offset = *root.get(row, col)?;
+
+                points.set(row, 1 + col, mean + offset)?;
+                points.set(row, 5 + col, mean - offset)?;
+            }
+        }
+
+        let weight = 1.0 / (2.0 * (n + lambda));
+        let mut mean_weights = vec![weight; 9];
+        let mut cov_weights = vec![weight; 9];
+
+        mean_weights[0] = lambda / (n + lambda);
+        cov_weights[0] = mean_weights[0] + 1.0 - self.alpha.powi(2) + self.beta;
+
+        Some((points, mean_weights, cov_weights))
+    }
+}
+
+impl Tracker for UnscentedKalmanFilter {
+    fn predict(&mut self, dt: f64) {
+        self.inner.predict(dt);
+    }
+
+    fn update(&mut self, anchors: &[Point], distances: &[f64], variances: &[f64]) {
+        let n = anchors.len();
+        let (points, mean_weights, cov_weights) = match self.sigma_points() {
+            Some(v) => v,
+            None => return,
+        };
+
+        // Ranges each sigma point would measure, one column per point
+        let projected = Matrix::from_iter(
+            n,
+            9,
+            anchors.iter().flat_map(|anchor| {
+                (0..9)
+                    .map(|col| {
+                        anchor.distance_to(&Point::new(
+                            *points.get(0, col).unwrap(),
+                            *points.get(1, col).unwrap(),
+                        ))
+                    })
+                    .collect::<Vec<_>>()
+            }),
+        );
+
+        let expected = Matrix::from_iter(
+            n,
+            1,
+            (0..n).map(|row| {
+                projected
+                    .get_row(row)
+                    .unwrap()
+                    .zip(&mean_weights)
+                    .map(|(value, weight)| value * weight)
+                    .sum()
+            }),
+        );
+
+        let mut innovation_cov = Matrix::from_diagonal(variances);
+        let mut cross_cov = Matrix::new(4, n);
+
+        for (col, weight) in cov_weights.iter().enumerate() {
+            let measurement_dev = Matrix::from_iter(
+                n,
+                1,
+                (0..n).map(|row| projected.get(row, col).unwrap() - expected.data[row]),
+            );
+            let state_dev = Matrix::from_iter(
+                4,
+                1,
+                (0..4).map(|row| points.get(row, col).unwrap() - self.inner.state.data[row]),
+            );
+            let measurement_dev_t = measurement_dev.transpose();
+
+            innovation_cov = (&innovation_cov
+                + &(&measurement_dev.dot(&measurement_dev_t).unwrap() * *weight))
+                .unwrap();
+            cross_cov =
+                (&cross_cov + &(&state_dev.dot(&measurement_dev_t).unwrap() * *weight)).unwrap();
+        }
+
+        let innovation = Matrix::from_iter(
+            n,
+            1,
+            distances
+                .iter()
+                .zip(&expected.data)
+                .map(|(dist, expected)| dist - expected),
+        );
+
+        if let Some(inverse) = innovation_cov.invert() {
+            let gain = cross_cov.dot(&inverse).unwrap();
+
+            self.inner.correct(&gain, &innovation, &innovation_cov);
+        }
+    }
+
+    fn position(&self) -> Point {
+        self.inner.position()
+    }
+
+    fn velocity(&self) -> Point {
+        self.inner.velocity()
+    }
+
+    fn position_covariance(&self) -> Matrix {
+        self.inner.position_covariance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Follows a tag moving at a constant velocity with exact ranges, returning the first and last
+    /// position errors and covariance traces
+    fn track(tracker: &mut impl Tracker) -> ([f64; 2], [f64; 2]) {
+        let anchors = [
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(0.0, 100.0),
+            Point::new(100.0, 100.0),
+        ];
+        let velocity = Point::new(2.0, 1.0);
+        let trace = |tracker: &dyn Tracker| tracker.position_covariance().trace().unwrap();
+
+        let mut tag = Point::new(20.0, 30.0);
+        let first = (tracker.position().distance_to(&tag), trace(tracker));
+
+        for _ in 0..30 {
+            tag = &tag + &velocity;
+            tracker.predict(1.0);
+
+            let distances = anchors
+                .iter()
+                .map(|anchor| anchor.distance_to(&tag))
+                .collect::<Vec<_>>();
+            tracker.update(&anchors, &distances, &[0.01; 4]);
+        }
+
+        assert!(tracker.velocity().distance_to(&velocity) < 0.1);
+
+        (
+            [first.0, tracker.position().distance_to(&tag)],
+            [first.1, trace(tracker)],
+        )
+    }
+
+    fn assert_converges(errors: [f64; 2], traces: [f64; 2]) {
+        assert!(errors[1] < 0.1, "{:?}", errors);
+        assert!(errors[1] < errors[0], "{:?}", errors);
+        assert!(traces[1] < 0.1 * traces[0], "{:?}", traces);
+    }
+
+    #[test]
+    fn ekf_converges_on_a_constant_velocity_tag() {
+        let (errors, traces) = track(&mut ExtendedKalmanFilter::new(
+            &Point::new(30.0, 40.0),
+            10.0,
+            5.0,
+            1e-3,
+        ));
+
+        assert_converges(errors, traces);
+    }
+
+    #[test]
+    fn ukf_converges_on_a_constant_velocity_tag() {
+        let (errors, traces) = track(&mut UnscentedKalmanFilter::new(
+            &Point::new(30.0, 40.0),
+            10.0,
+            5.0,
+            1e-3,
+        ));
+
+        assert_converges(errors, traces);
+    }
+}
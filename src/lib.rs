@@ -0,0 +1,30 @@
+pub mod aoa;
+pub mod circle;
+pub mod comparison;
+pub mod heatmap;
+pub mod kalman;
+pub mod localizer;
+pub mod matrix;
+pub mod multilateration;
+pub mod noise;
+pub mod particle_filter;
+pub mod placement;
+pub mod plot;
+pub mod point;
+pub mod progress;
+pub mod rectangle;
+pub mod replay;
+pub mod report;
+pub mod robust;
+pub mod rssi;
+pub mod scenario;
+pub mod scene;
+pub mod sink;
+pub mod stats;
+pub mod sweep;
+pub mod tdoa;
+pub mod test_runner;
+pub mod trajectory;
+pub mod trial_csv;
+pub mod two_dim_shape;
+pub mod uncertainty;
@@ -0,0 +1,232 @@
+use std::{fmt::Display, str::FromStr};
+
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    aoa::{self, AoaCallback, AoaMeasurement, HybridCallback},
+    multilateration, particle_filter,
+    point::Point,
+    robust,
+    rssi::PathLossModel,
+    tdoa::{self, TdoaCallback, TdoaMeasurement},
+    test_runner::{SimulationMode, TestResult, TestRunner, TestRunnerCallback},
+};
+
+/// Why a trial got no fix
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SolveError {
+    /// Fewer anchors were received than the solver needs
+    TooFewAnchors,
+    /// The solver gave up, such as on degenerate anchor geometry
+    NoFix,
+    /// The localizer consumes bearings but the trial has none
+    MissingBearings,
+}
+
+impl SolveError {
+    /// Name as written to trial files
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolveError::TooFewAnchors => "too_few_anchors",
+            SolveError::NoFix => "no_fix",
+            SolveError::MissingBearings => "missing_bearings",
+        }
+    }
+}
+
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::TooFewAnchors => write!(f, "Too few anchors received"),
+            SolveError::NoFix => write!(f, "Solver found no fix"),
+            SolveError::MissingBearings => write!(f, "No bearings were measured"),
+        }
+    }
+}
+
+impl FromStr for SolveError {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            SolveError::TooFewAnchors,
+            SolveError::NoFix,
+            SolveError::MissingBearings,
+        ]
+        .into_iter()
+        .find(|err| err.name() == s)
+        .ok_or_else(|| format!("Unknown solve error {}", s))
+    }
+}
+
+/// A solver along with the kind of measurements it consumes
+#[derive(Debug, Clone, Copy)]
+pub enum Localizer {
+    Range(TestRunnerCallback),
+    Tdoa(TdoaCallback),
+    Aoa(AoaCallback),
+    Hybrid(HybridCallback),
+}
+
+/// Every localizer, by the name of its function
+pub const LOCALIZERS: [(&str, Localizer); 9] = [
+    ("paper_way", Localizer::Range(multilateration::paper_way)),
+    ("ransac_way", Localizer::Range(robust::ransac_way)),
+    ("huber_way", Localizer::Range(robust::huber_way)),
+    ("tukey_way", Localizer::Range(robust::tukey_way)),
+    (
+        "particle_way",
+        Localizer::Range(particle_filter::particle_way),
+    ),
+    ("chan_taylor", Localizer::Tdoa(tdoa::chan_taylor)),
+    ("bearing_way", Localizer::Aoa(aoa::bearing_way)),
+    ("hybrid_way", Localizer::Hybrid(aoa::hybrid_way)),
+    ("polar_fix", Localizer::Hybrid(aoa::polar_fix)),
+];
+
+impl Localizer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        LOCALIZERS
+            .iter()
+            .find(|(localizer_name, _)| *localizer_name == name)
+            .map(|(_, localizer)| *localizer)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        LOCALIZERS.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Fewest anchors this localizer can fix the tag from, see [`SimulationMode::min_anchors`]
+    pub fn min_anchors(&self) -> usize {
+        match self {
+            Localizer::Range(_) | Localizer::Tdoa(_) => 3,
+            Localizer::Aoa(_) => 2,
+            Localizer::Hybrid(_) => 1,
+        }
+    }
+
+    /// Describes the kind of measurements this localizer consumes
+    pub fn measurements(&self) -> &'static str {
+        match self {
+            Localizer::Range(_) => "ranges",
+            Localizer::Tdoa(_) => "range differences",
+            Localizer::Aoa(_) => "bearings",
+            Localizer::Hybrid(_) => "ranges and bearings",
+        }
+    }
+
+    /// Whether this localizer can solve the measurements of `result`. Range localizers cannot
+    /// take the pseudo ranges of a TDoA trial, and TDoA localizers difference plain ranges.
+    pub fn accepts(&self, result: &TestResult) -> bool {
+        match self {
+            Localizer::Range(_) => result.tdoa.is_none(),
+            Localizer::Tdoa(_) => true,
+            Localizer::Aoa(_) | Localizer::Hybrid(_) => result.aoa.is_some(),
+        }
+    }
+
+    /// Whether this localizer can solve the measurements taken in `mode`, see
+    /// [`Localizer::accepts`]
+    pub fn accepts_mode(&self, mode: &SimulationMode) -> bool {
+        match self {
+            Localizer::Range(_) => !matches!(mode, SimulationMode::Tdoa { .. }),
+            Localizer::Tdoa(_) => true,
+            Localizer::Aoa(_) | Localizer::Hybrid(_) => matches!(
+                mode,
+                SimulationMode::Aoa { .. } | SimulationMode::Hybrid { .. }
+            ),
+        }
+    }
+
+    /// Whether this localizer can take ranges converted from RSSI readings, see
+    /// [`SimulationMode::Rssi`]
+    pub fn takes_rssi(&self) -> bool {
+        matches!(self, Localizer::Range(_))
+    }
+
+    /// Points `runner` at this localizer, switching it to the mode with the measurements it
+    /// needs. TDoA differences are taken relative to the first anchor, and range localizers
+    /// get their ranges from RSSI readings when there is a `path_loss` model.
+    pub fn configure<R: Rng + SeedableRng>(
+        &self,
+        runner: &mut TestRunner<R>,
+        angle_std: f64,
+        clock_offset_std: f64,
+        path_loss: Option<PathLossModel>,
+    ) {
+        runner.mode = match *self {
+            Localizer::Range(callback) => {
+                runner.callback = callback;
+
+                match path_loss {
+                    Some(model) => SimulationMode::Rssi { model },
+                    None => SimulationMode::Range,
+                }
+            }
+            Localizer::Tdoa(callback) => SimulationMode::Tdoa {
+                reference: 0,
+                clock_offset_std,
+                callback,
+            },
+            Localizer::Aoa(callback) => SimulationMode::Aoa {
+                angle_std,
+                callback,
+            },
+            Localizer::Hybrid(callback) => SimulationMode::Hybrid {
+                angle_std,
+                callback,
+            },
+        };
+    }
+
+    /// Fix from raw measurements. TDoA localizers take `distances` as pseudo ranges, differenced
+    /// against the first anchor, and bearings are only used by the modes that measure them.
+    pub fn solve(
+        &self,
+        anchors: &[Point],
+        distances: &[f64],
+        bearings: &[f64],
+    ) -> Result<Point, SolveError> {
+        if anchors.len() < self.min_anchors() {
+            return Err(SolveError::TooFewAnchors);
+        }
+
+        let fix = match *self {
+            Localizer::Range(callback) => callback(anchors, distances),
+            Localizer::Tdoa(callback) => {
+                callback(anchors, &TdoaMeasurement::from_ranges(0, distances))
+            }
+            Localizer::Aoa(callback) => callback(anchors, &AoaMeasurement::new(bearings.to_vec())),
+            Localizer::Hybrid(callback) => {
+                callback(anchors, distances, &AoaMeasurement::new(bearings.to_vec()))
+            }
+        };
+
+        fix.ok_or(SolveError::NoFix)
+    }
+
+    /// Fix from the measurements of a trial that were not dropped. TDoA localizers difference the
+    /// ranges when the trial has no TDoA measurement of its own.
+    pub fn solve_measurements(&self, result: &TestResult) -> Result<Point, SolveError> {
+        let (anchors, distances) = result.received();
+
+        if anchors.len() < self.min_anchors() {
+            return Err(SolveError::TooFewAnchors);
+        }
+
+        let fix = match (*self, &result.aoa) {
+            (Localizer::Range(callback), _) => callback(&anchors, &distances),
+            (Localizer::Tdoa(callback), _) => match &result.tdoa {
+                Some(tdoa) => callback(&anchors, tdoa),
+                None => callback(&anchors, &TdoaMeasurement::from_ranges(0, &distances)),
+            },
+            (Localizer::Aoa(callback), Some(aoa)) => callback(&anchors, aoa),
+            (Localizer::Hybrid(callback), Some(aoa)) => callback(&anchors, &distances, aoa),
+            (Localizer::Aoa(_) | Localizer::Hybrid(_), None) => {
+                return Err(SolveError::MissingBearings)
+            }
+        };
+
+        fix.ok_or(SolveError::NoFix)
+    }
+}
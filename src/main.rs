@@ -1,50 +1,31 @@
-use std::fs;
-
-// use circle::{Circle, CircleIntersectionError};
-use matrix::Matrix;
-use rectangle::Rectangle;
-use test_runner::TestRunner;
-
-mod circle;
-mod matrix;
-mod point;
-mod rectangle;
-mod test_runner;
-mod two_dim_shape;
-
-use crate::{point::Point, two_dim_shape::TwoDimShape};
-
-fn paper_way(anchors: &Vec<Point>, distances: &Vec<f64>) -> Point {
-    let n = anchors.len();
-
-    let mut a = Matrix::new(n - 1, 2);
-    let mut b = Matrix::new(n - 1, 1); // vec![0.0; n];
-
-    for i in 1..n {
-        *a.get_mut(i - 1, 0).unwrap() = anchors[i].x - anchors[0].x;
-        *a.get_mut(i - 1, 1).unwrap() = anchors[i].y - anchors[0].y;
-
-        *b.get_mut(i - 1, 0).unwrap() = distances[0].powi(2) - distances[i].powi(2)
-            + anchors[i].pow(2).sum()
-            - anchors[0].pow(2).sum();
-    }
-
-    let a_t = a.transpose();
-    let result = a_t
-        .dot(&a)
-        .unwrap()
-        .invert()
-        .unwrap()
-        .dot(&a_t)
-        .unwrap()
-        .dot(&(&b / 2.0))
-        .unwrap();
-
-    assert!(result.n_rows == 2);
-    assert!(result.n_cols == 1);
-
-    Point::new(*result.get(0, 0).unwrap(), *result.get(1, 0).unwrap())
-}
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    process, thread,
+};
+
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand};
+// use matrix::circle::{Circle, CircleIntersectionError};
+use matrix::{
+    comparison::ComparisonReport,
+    localizer::Localizer,
+    multilateration::paper_way,
+    placement::AnchorPlacement,
+    plot,
+    point::Point,
+    progress::ProgressBar,
+    rectangle::Rectangle,
+    replay::{replay, FailureCapture},
+    report::{ExperimentConfig, ExperimentReport, ReportFormat},
+    rssi::PathLossModel,
+    scenario::{PlacementKind, Scenario},
+    scene::Scene,
+    sweep::Sweep,
+    test_runner::{SimulationMode, TestRunner},
+    trial_csv::{TrialReader, TrialWriter},
+    two_dim_shape::TwoDimShape,
+};
 
 /*
 fn circle_way(anchors: &Vec<Point>, distances: &Vec<f64>) -> Point {
@@ -168,141 +149,928 @@ fn circle_way(anchors: &Vec<Point>, distances: &Vec<f64>) -> Point {
     //     assert_eq!(index, correct, "wrong index");
 } */
 
-fn calculate_mean(numbers: &Vec<f64>) -> f64 {
-    let sum: f64 = numbers.iter().sum();
-    sum / numbers.len() as f64
+/// Trials drawn in the scatter plot, past a handful their range circles hide everything else
+const SCATTER_TRIALS: usize = 20;
+
+#[derive(Parser)]
+#[command(about = "Simulates locating a tag from noisy anchor measurements")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn calculate_median(numbers: &Vec<f64>) -> f64 {
-    let mut sorted_numbers = numbers.clone();
-    sorted_numbers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+#[derive(Subcommand)]
+enum Command {
+    /// Runs trials with one localizer and reports its accuracy
+    Simulate {
+        #[command(flatten)]
+        run: RunArgs,
+        #[arg(long, value_parser = parse_times, default_value_t = 1_000_000)]
+        times: i32,
+        #[arg(long, value_parser = parse_localizer, default_value = "paper_way")]
+        localizer: String,
+        /// Also writes every trial to this CSV file
+        #[arg(long)]
+        trials_csv: Option<PathBuf>,
+        /// Also draws the error histogram and CDF and a scatter of the first trials as SVG files
+        /// in this directory
+        #[arg(long)]
+        plots: Option<PathBuf>,
+        #[command(flatten)]
+        failures: FailureArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Maps the mean, median and 95th percentile error over a grid of tag positions, as CSV
+    Heatmap {
+        #[command(flatten)]
+        run: RunArgs,
+        #[command(flatten)]
+        grid: GridArgs,
+        #[arg(long, value_parser = parse_localizer, default_value = "paper_way")]
+        localizer: String,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        /// Standard output when not set
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Also draws the mean error of every cell to this SVG file
+        #[arg(long)]
+        svg: Option<PathBuf>,
+    },
+    /// Runs several localizers on the same trials and reports their paired differences
+    Compare {
+        #[command(flatten)]
+        run: RunArgs,
+        #[arg(long, value_parser = parse_times, default_value_t = 100_000)]
+        times: i32,
+        #[arg(
+            long,
+            value_parser = parse_localizer,
+            value_delimiter = ',',
+            default_value = "paper_way,huber_way"
+        )]
+        localizers: Vec<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Runs one localizer over a grid of error margins, anchor counts and bounds sizes, with a
+    /// row of summary statistics per configuration (CSV as text)
+    Sweep {
+        #[command(flatten)]
+        run: RunArgs,
+        #[arg(long, value_parser = parse_times, default_value_t = 10_000)]
+        times: i32,
+        #[arg(long, value_parser = parse_localizer, default_value = "paper_way")]
+        localizer: String,
+        /// Comma separated, `--error-margin` when not set
+        #[arg(long, value_parser = parse_non_negative, value_delimiter = ',')]
+        error_margins: Vec<f64>,
+        /// Comma separated, `--anchors` when not set
+        #[arg(long, value_parser = parse_anchor_count, value_delimiter = ',')]
+        anchor_counts: Vec<i32>,
+        /// Comma separated sides of square bounds at the corner of `--bounds`, `--bounds` when
+        /// not set
+        #[arg(long, value_parser = parse_positive, value_delimiter = ',')]
+        sizes: Vec<f64>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Regenerates a single trial and draws its anchors, ranges, tag and estimate as SVG
+    Trial {
+        /// The same settings and `--seed` as the run the trial came from
+        #[command(flatten)]
+        run: RunArgs,
+        #[arg(long, value_parser = parse_localizer, default_value = "paper_way")]
+        localizer: String,
+        #[arg(long)]
+        iteration: u64,
+        /// Standard output when not set
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Solves the trials of a replay file again and prints them in full.
+    ///
+    /// The file does not keep the noise the trials were captured with, so the uncertainty of
+    /// every fix is recomputed from the noise options given here.
+    Replay {
+        file: PathBuf,
+        #[arg(long, value_parser = parse_localizer, default_value = "paper_way")]
+        localizer: String,
+        /// Also draws every trial as an SVG file in this directory
+        #[arg(long)]
+        scenes: Option<PathBuf>,
+        /// The noise of the run the trials came from, for the uncertainty of every fix
+        #[command(flatten)]
+        noise: NoiseArgs,
+    },
+    /// Runs the experiment described by a TOML or JSON scenario file
+    Run { scenario: PathBuf },
+    /// Locates a tag from measurements given on the command line
+    Solve {
+        #[arg(long, value_parser = parse_localizer, default_value = "paper_way")]
+        localizer: String,
+        /// Anchor as `x,y`, once per anchor
+        #[arg(long = "anchor", value_parser = parse_point, required = true)]
+        anchors: Vec<Point>,
+        /// Distance to each anchor, in the order of the anchors
+        #[arg(long = "distance", value_parser = parse_non_negative)]
+        distances: Vec<f64>,
+        /// Bearing from each anchor in radians, for the AoA and hybrid localizers
+        #[arg(long = "bearing", allow_negative_numbers = true)]
+        bearings: Vec<f64>,
+    },
+}
 
-    let mid = sorted_numbers.len() / 2;
-    if sorted_numbers.len() % 2 == 0 {
-        (sorted_numbers[mid - 1] + sorted_numbers[mid]) / 2.0
-    } else {
-        sorted_numbers[mid]
+/// How the simulated trials are set up
+#[derive(Args)]
+struct RunArgs {
+    /// At least 2
+    #[arg(long, value_parser = parse_anchor_count, default_value_t = 3)]
+    anchors: i32,
+    /// Region of the anchors and tags as `x,y,width,height`, with a positive width and height
+    #[arg(long, value_parser = parse_bounds, default_value = "100,100,500,500")]
+    bounds: Rectangle,
+    /// Random when not set
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Every available core when not set
+    #[arg(long)]
+    threads: Option<usize>,
+    #[command(flatten)]
+    noise: NoiseArgs,
+}
+
+impl RunArgs {
+    /// Exits with a validation error on `subcommand` when the noise options do not fit
+    /// `localizer`
+    fn runner(&self, subcommand: &str, localizer: &str) -> TestRunner {
+        let mut runner =
+            self.noise
+                .runner(subcommand, localizer, self.anchors, self.bounds.clone());
+
+        runner.progress = Box::new(ProgressBar::new());
+        runner.threads = self
+            .threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+        if let Some(seed) = self.seed {
+            runner.seed = seed;
+        }
+
+        runner
     }
 }
 
-fn calculate_std(numbers: &Vec<f64>) -> f64 {
-    let mean = calculate_mean(numbers);
-    let variance =
-        numbers.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (numbers.len() as f64 - 1.0);
-    variance.sqrt()
+/// How noisy the measurements are
+#[derive(Args)]
+struct NoiseArgs {
+    #[arg(long, value_parser = parse_non_negative, default_value_t = 0.05)]
+    error_margin: f64,
+    /// Bearing noise in radians, for the AoA and hybrid localizers
+    #[arg(long, value_parser = parse_non_negative, default_value_t = 0.05)]
+    angle_std: f64,
+    /// Clock offset noise as a distance, for the TDoA localizers
+    #[arg(long, value_parser = parse_non_negative, default_value_t = 1.0)]
+    clock_offset_std: f64,
+    /// Ranges come from RSSI readings of a log-distance path loss model instead of
+    /// `--error-margin` noise, for the range localizers
+    #[arg(long)]
+    rssi: bool,
+    /// Path loss exponent of `--rssi`, 2 in free space
+    #[arg(long, value_parser = parse_positive, default_value_t = PathLossModel::default().exponent)]
+    path_loss_exponent: f64,
+    /// Received power of `--rssi` in dBm at 1m
+    #[arg(
+        long,
+        value_parser = parse_finite,
+        allow_negative_numbers = true,
+        default_value_t = PathLossModel::default().reference_power
+    )]
+    reference_power: f64,
+    /// Shadowing of `--rssi` in dB
+    #[arg(long, value_parser = parse_non_negative, default_value_t = PathLossModel::default().shadowing_std)]
+    shadowing_std: f64,
 }
 
-fn identify_outliers(numbers: &Vec<f64>, threshold: f64) -> Vec<f64> {
-    let mean = calculate_mean(numbers);
-    let std = calculate_std(numbers);
-    let z_scores: Vec<f64> = numbers.iter().map(|&x| (x - mean) / std).collect();
+impl NoiseArgs {
+    /// The model of `--rssi`, `None` without it
+    fn path_loss(&self) -> Option<PathLossModel> {
+        self.rssi.then(|| {
+            PathLossModel::new(
+                self.path_loss_exponent,
+                self.reference_power,
+                self.shadowing_std,
+            )
+        })
+    }
 
-    z_scores
-        .iter()
-        .zip(numbers.iter())
-        .filter(|(z_score, _)| z_score.abs() > threshold)
-        .map(|(_, &x)| x)
-        .collect()
+    fn check(&self, localizer: &str) -> Result<(), String> {
+        match Localizer::from_name(localizer) {
+            Some(found) if self.rssi && !found.takes_rssi() => Err(format!(
+                "expected --rssi only with localizers of ranges, {} takes {}",
+                localizer,
+                found.measurements()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Exits with a validation error on `subcommand` when these options do not fit `localizer`
+    fn runner(
+        &self,
+        subcommand: &str,
+        localizer: &str,
+        anchors: i32,
+        bounds: Rectangle,
+    ) -> TestRunner {
+        if let Err(err) = self.check(localizer) {
+            validation_error(subcommand, err);
+        }
+
+        let localizer = Localizer::from_name(localizer).expect("Bad localizer name");
+        let mut runner = TestRunner::new(anchors, self.error_margin, bounds, paper_way);
+
+        localizer.configure(
+            &mut runner,
+            self.angle_std,
+            self.clock_offset_std,
+            self.path_loss(),
+        );
+
+        runner
+    }
 }
 
-fn main() {
-    let num_of_anchors = 3;
-    let error_margin = 0.05;
-    let bounds = Rectangle::new(100.0, 100.0, 500.0, 500.0);
-    let callback = paper_way;
+/// Cells of a heatmap and the trials run with the tag at the centre of every one
+#[derive(Args)]
+struct GridArgs {
+    #[arg(long, value_parser = parse_cells, default_value_t = 10)]
+    cols: usize,
+    #[arg(long, value_parser = parse_cells, default_value_t = 10)]
+    rows: usize,
+    #[arg(long, value_parser = parse_cells, default_value_t = 100)]
+    trials_per_cell: usize,
+}
 
-    let mut runner = TestRunner::new(num_of_anchors, error_margin, bounds.clone(), callback);
+/// Anchor layout kept for every trial of a heatmap, a random one would blur the map
+#[derive(Args)]
+struct LayoutArgs {
+    /// One of corners, perimeter, grid, fixed
+    #[arg(long, value_parser = parse_layout, default_value = "corners")]
+    placement: PlacementKind,
+    /// Anchor of the fixed placement as `x,y`, once per anchor
+    #[arg(long = "anchor", value_parser = parse_point)]
+    anchor_pts: Vec<Point>,
+}
 
-    let times = 1_000_000;
-    let result: Vec<test_runner::TestResult> = runner.run(times);
+impl LayoutArgs {
+    fn placement(&self) -> AnchorPlacement {
+        self.placement.placement(self.anchor_pts.clone())
+    }
 
-    let deltas = result.iter().map(|v| v.delta);
-    let vec = deltas.clone().collect::<Vec<_>>();
+    fn check(&self) -> Result<(), String> {
+        match (self.placement, self.anchor_pts.len()) {
+            (PlacementKind::Fixed, n) if n < 2 => Err(format!(
+                "expected an --anchor for each of at least 2 anchors of the fixed placement, got {}",
+                n
+            )),
+            (PlacementKind::Fixed, _) | (_, 0) => Ok(()),
+            _ => Err("expected --anchor only with the fixed placement".to_string()),
+        }
+    }
+}
 
-    let mean = calculate_mean(&vec);
-    let median = calculate_median(&vec);
-    let std = calculate_std(&vec);
-    let outliers = identify_outliers(&vec, 2.0).len();
+/// Where the failed trials of a run are captured for replaying
+#[derive(Args)]
+struct FailureArgs {
+    /// Also writes the trials off by more than `--failure-threshold`, or without a fix, to this
+    /// replay file
+    #[arg(long)]
+    failures: Option<PathBuf>,
+    /// The bounds' largest span when not set
+    #[arg(long)]
+    failure_threshold: Option<f64>,
+}
 
-    println!("\nMean: {:.2}", mean);
-    println!("Median: {:.2}", median);
-    println!("Standard Deviation: {:.2}", std);
+#[derive(Args)]
+struct OutputArgs {
+    /// One of text, json, markdown
+    #[arg(long, default_value = "text")]
+    format: ReportFormat,
+    /// Standard output when not set
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
 
-    println!(
-        "\nNum Outliers: {} / {} ({:.2}%)",
-        outliers,
-        times,
-        (outliers as f64 / times as f64) * 100.0
+impl OutputArgs {
+    fn write(&self, content: &str) {
+        write_output(&self.output, content);
+    }
+}
+
+fn write_output(path: &Option<PathBuf>, content: &str) {
+    match path {
+        Some(path) => fs::write(path, content).expect("Bad file write"),
+        None => println!("{}", content),
+    }
+}
+
+fn parse_localizer(name: &str) -> Result<String, String> {
+    match Localizer::from_name(name) {
+        Some(_) => Ok(name.to_string()),
+        None => Err(format!(
+            "unknown localizer, expected one of {}",
+            Localizer::names().join(", ")
+        )),
+    }
+}
+
+fn parse_anchor_count(s: &str) -> Result<i32, String> {
+    match s.parse::<i32>().map_err(|err| err.to_string())? {
+        count if count < 2 => Err("expected at least 2 anchors".to_string()),
+        count => Ok(count),
+    }
+}
+
+fn parse_times(s: &str) -> Result<i32, String> {
+    match s.parse::<i32>().map_err(|err| err.to_string())? {
+        times if times < 1 => Err("expected at least 1 trial".to_string()),
+        times => Ok(times),
+    }
+}
+
+fn parse_cells(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>().map_err(|err| err.to_string())? {
+        0 => Err("expected at least 1".to_string()),
+        count => Ok(count),
+    }
+}
+
+fn parse_finite(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>().map_err(|err| err.to_string())? {
+        v if v.is_finite() => Ok(v),
+        _ => Err("expected a finite number".to_string()),
+    }
+}
+
+fn parse_non_negative(s: &str) -> Result<f64, String> {
+    match parse_finite(s)? {
+        v if v >= 0.0 => Ok(v),
+        _ => Err("expected a number of at least 0".to_string()),
+    }
+}
+
+fn parse_positive(s: &str) -> Result<f64, String> {
+    match parse_finite(s)? {
+        v if v > 0.0 => Ok(v),
+        _ => Err("expected a number above 0".to_string()),
+    }
+}
+
+fn parse_values(s: &str, count: usize) -> Result<Vec<f64>, String> {
+    let values = s
+        .split(',')
+        .map(parse_finite)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if values.len() != count {
+        return Err(format!("expected {} comma separated numbers", count));
+    }
+
+    Ok(values)
+}
+
+fn parse_layout(s: &str) -> Result<PlacementKind, String> {
+    match s.parse()? {
+        PlacementKind::Random => Err(
+            "expected one of corners, perimeter, grid, fixed, which keep the anchors put"
+                .to_string(),
+        ),
+        kind => Ok(kind),
+    }
+}
+
+fn parse_point(s: &str) -> Result<Point, String> {
+    let values = parse_values(s, 2)?;
+
+    Ok(Point::new(values[0], values[1]))
+}
+
+fn parse_bounds(s: &str) -> Result<Rectangle, String> {
+    let values = parse_values(s, 4)?;
+
+    if values[2] <= 0.0 || values[3] <= 0.0 {
+        return Err("expected a width and height above 0".to_string());
+    }
+
+    Ok(Rectangle::new(values[0], values[1], values[2], values[3]))
+}
+
+fn create_trials(path: &Path, anchors: usize) -> TrialWriter<File> {
+    TrialWriter::new(File::create(path).expect("Bad file create"), anchors).expect("Bad file write")
+}
+
+/// A single report as is, several as a JSON array or one after the other
+fn render_reports(reports: &[ExperimentReport], format: ReportFormat) -> String {
+    match (reports, format) {
+        ([report], _) => report.render(format),
+        (_, ReportFormat::Json) => {
+            serde_json::to_string_pretty(reports).expect("Bad report serialization")
+        }
+        _ => reports
+            .iter()
+            .map(|report| report.render(format))
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+    }
+}
+
+/// Writes `histogram.svg` and `cdf.svg` of the `deltas` of a run and `scatter.svg` of its first
+/// trials to the `dir` directory
+fn write_plots(dir: &Path, deltas: &[f64], runner: &TestRunner) {
+    let scatter_trials = runner
+        .trials(deltas.len().min(SCATTER_TRIALS) as u64)
+        .collect::<Vec<_>>();
+
+    fs::create_dir_all(dir).expect("Bad directory create");
+    fs::write(dir.join("histogram.svg"), plot::histogram(deltas, 70)).expect("Bad file write");
+    fs::write(dir.join("cdf.svg"), plot::cdf(deltas)).expect("Bad file write");
+    fs::write(
+        dir.join("scatter.svg"),
+        plot::scatter(&scatter_trials, runner.bounds()),
+    )
+    .expect("Bad file write");
+}
+
+/// Streams the trials to the files asked for, keeping nothing but their deltas in memory
+fn simulate(
+    run: &RunArgs,
+    times: i32,
+    localizer: &str,
+    trials_csv: &Option<PathBuf>,
+    plots: &Option<PathBuf>,
+    failures: &FailureArgs,
+    output: &OutputArgs,
+) {
+    let runner = run.runner("simulate", localizer);
+    let anchors = runner.anchor_count();
+    let threshold = failures
+        .failure_threshold
+        .unwrap_or_else(|| runner.bounds().calc_max_span());
+
+    let mut deltas = Vec::<f64>::new();
+    let mut trials = trials_csv.as_ref().map(|path| create_trials(path, anchors));
+    let mut capture = failures.failures.as_ref().map(|path| {
+        FailureCapture::new(
+            File::create(path).expect("Bad file create"),
+            anchors,
+            threshold,
+        )
+        .expect("Bad file write")
+    });
+
+    runner
+        .run_into(times, &mut (&mut deltas, &mut trials, &mut capture))
+        .expect("Bad file write");
+
+    if let Some(trials) = trials {
+        trials.finish().expect("Bad file write");
+    }
+
+    if let Some(capture) = capture {
+        eprintln!("Captured {} failed trials", capture.captured);
+        capture.finish().expect("Bad file write");
+    }
+
+    if let Some(dir) = plots {
+        write_plots(dir, &deltas, &runner);
+    }
+
+    let report = ExperimentReport::new(
+        ExperimentConfig::new(localizer, &runner, deltas.len()),
+        &deltas,
     );
 
-    let completely_wrong = vec
+    output.write(&report.render(output.format));
+}
+
+fn heatmap(
+    run: &RunArgs,
+    grid: &GridArgs,
+    localizer: &str,
+    layout: &LayoutArgs,
+    output: &Option<PathBuf>,
+    svg: &Option<PathBuf>,
+) {
+    if let Err(err) = layout.check() {
+        validation_error("heatmap", err);
+    }
+
+    let mut runner = run.runner("heatmap", localizer);
+    runner.placement = layout.placement();
+
+    let heatmap = runner.run_heatmap(grid.cols, grid.rows, grid.trials_per_cell);
+
+    if let Some(path) = svg {
+        fs::write(
+            path,
+            plot::heatmap(&heatmap.bounds, &heatmap.mean, "Mean Error"),
+        )
+        .expect("Bad file write");
+    }
+
+    write_output(output, &heatmap.to_csv());
+}
+
+/// Every localizer gets the same anchors, tag and measurements every trial
+fn compare(run: &RunArgs, times: i32, localizers: &[String], output: &OutputArgs) {
+    let named = localizers
+        .iter()
+        .map(|name| {
+            (
+                name.as_str(),
+                Localizer::from_name(name).expect("Bad localizer name"),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Only the AoA and hybrid modes measure bearings, and their ranges are as good as any
+    let mode = named
         .iter()
-        .filter(|v| **v > bounds.calc_max_span())
-        .collect::<Vec<_>>()
-        .len();
+        .find(|(_, localizer)| matches!(localizer, Localizer::Aoa(_) | Localizer::Hybrid(_)))
+        .unwrap_or(&named[0]);
+
+    let runner = run.runner("compare", mode.0);
 
-    println!(
-        "\nCompletely Wrong: {} / {} ({:.2}%)",
-        completely_wrong,
+    if let Err(err) = check_compare(&named, mode.0, &runner.mode) {
+        validation_error("compare", err);
+    }
+
+    let comparison = runner.run_comparison(times, &named);
+    let report = ComparisonReport::new(
+        ExperimentConfig::new(&localizers.join(", "), &runner, comparison.trials()),
+        &comparison,
+    );
+
+    output.write(&report.render(output.format));
+}
+
+fn sweep(
+    run: &RunArgs,
+    times: i32,
+    localizer: &str,
+    error_margins: &[f64],
+    anchor_counts: &[i32],
+    sizes: &[f64],
+    output: &OutputArgs,
+) {
+    // Every unset list falls back to the single value of the run
+    let error_margins = match error_margins {
+        [] => vec![run.noise.error_margin],
+        margins => margins.to_vec(),
+    };
+    let bounds = match sizes {
+        [] => vec![run.bounds.clone()],
+        sizes => sizes
+            .iter()
+            .map(|size| Rectangle::new(run.bounds.pt.x, run.bounds.pt.y, *size, *size))
+            .collect(),
+    };
+    let anchor_counts = match anchor_counts {
+        [] => vec![run.anchors],
+        counts => counts.to_vec(),
+    };
+
+    let report = Sweep::new(error_margins, anchor_counts, bounds).run(
+        &mut run.runner("sweep", localizer),
+        localizer,
         times,
-        completely_wrong as f64 / times as f64 * 100.0
     );
-    let n_non_compliant_5 = vec
+
+    output.write(&report.render(output.format));
+}
+
+fn trial(run: &RunArgs, localizer: &str, iteration: u64, output: &Option<PathBuf>) {
+    let result = run.runner("trial", localizer).run_iteration(iteration);
+
+    eprintln!("{}", result);
+    write_output(output, &Scene::from_result(&result).to_svg());
+}
+
+fn values_str(values: &[f64]) -> String {
+    values
         .iter()
-        .filter(|v| {
-            v.partial_cmp(&&(bounds.calc_max_span() * 0.05))
-                .unwrap()
-                .is_gt()
-        })
-        .collect::<Vec<_>>()
-        .len();
-    let n_non_compliant_10 = vec
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn replay_failures(file: &Path, localizer: &str, scenes: &Option<PathBuf>, noise: &NoiseArgs) {
+    let reader = TrialReader::new(BufReader::new(File::open(file).expect("Bad file open")))
+        .unwrap_or_else(|err| {
+            eprintln!("{}: {}", file.display(), err);
+            process::exit(1);
+        });
+    let localizer_fn = Localizer::from_name(localizer).expect("Bad localizer name");
+    // Only the noise and mode matter to the uncertainty, not where the anchors would be placed
+    let runner = noise.runner(
+        "replay",
+        localizer,
+        reader.anchors() as i32,
+        Rectangle::new(0.0, 0.0, 1.0, 1.0),
+    );
+
+    if let Some(dir) = scenes {
+        fs::create_dir_all(dir).expect("Bad directory create");
+    }
+
+    for replayed in replay(reader, localizer_fn, &runner) {
+        let replayed = replayed.unwrap_or_else(|err| {
+            eprintln!("{}: {}", file.display(), err);
+            process::exit(1);
+        });
+        let result = &replayed.result;
+
+        println!("{}", replayed.original);
+        println!(
+            "Replayed With {}: Predicted Pt: {}, Delta: {} (was {})",
+            localizer, result.predicted_pt, result.delta, replayed.original.delta
+        );
+
+        match (&result.failure, &result.uncertainty) {
+            (Some(failure), _) => println!("Replay Failure: {}", failure),
+            (None, Some(uncertainty)) => println!("Replay Uncertainty: {}", uncertainty),
+            (None, None) => println!("Replay Uncertainty: None"),
+        }
+
+        if result.failure.is_none() {
+            let range_residuals = result.range_residuals();
+            println!("Range Residuals: {}", values_str(&range_residuals));
+
+            if let Some(residuals) = result.range_difference_residuals() {
+                println!("Range Difference Residuals: {}", values_str(&residuals));
+            }
+
+            if let Some(residuals) = result.bearing_residuals() {
+                println!("Bearing Residuals: {}", values_str(&residuals));
+            }
+
+            // The anchor most at odds with the fix, usually the one to look at first
+            let worst = range_residuals
+                .iter()
+                .enumerate()
+                .filter(|(_, residual)| !residual.is_nan())
+                .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()));
+
+            if let Some((index, residual)) = worst {
+                println!("Largest Range Residual: Anchor #{} ({})", index, residual);
+            }
+        }
+
+        println!();
+
+        if let Some(dir) = scenes {
+            fs::write(
+                dir.join(format!("trial_{}.svg", result.iteration)),
+                Scene::from_result(result).to_svg(),
+            )
+            .expect("Bad file write");
+        }
+    }
+}
+
+/// Runs every localizer of the scenario at `path` on the same trials
+fn run_scenario(path: &Path) {
+    let mut scenario = Scenario::load(path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path.display(), err);
+        process::exit(1);
+    });
+    scenario.seed.get_or_insert_with(rand::random);
+
+    let reports = scenario
+        .localizers
         .iter()
-        .filter(|v| {
-            v.partial_cmp(&&(bounds.calc_max_span() * 0.10))
-                .unwrap()
-                .is_gt()
+        .map(|localizer| {
+            let mut runner = scenario.runner(localizer);
+            runner.progress = Box::new(ProgressBar::new());
+
+            // One file per localizer, named after it when there are several
+            let mut trials = scenario.output.trials_csv.as_ref().map(|csv_path| {
+                if scenario.localizers.len() > 1 {
+                    let stem = csv_path.file_stem().unwrap_or_default().to_string_lossy();
+                    create_trials(
+                        &csv_path.with_file_name(format!("{}_{}.csv", stem, localizer.name)),
+                        runner.anchor_count(),
+                    )
+                } else {
+                    create_trials(csv_path, runner.anchor_count())
+                }
+            });
+
+            let mut deltas = Vec::<f64>::new();
+            runner
+                .run_into(scenario.trials as i32, &mut (&mut deltas, &mut trials))
+                .expect("Bad file write");
+
+            if let Some(trials) = trials {
+                trials.finish().expect("Bad file write");
+            }
+
+            ExperimentReport::new(
+                ExperimentConfig::new(&localizer.name, &runner, deltas.len()),
+                &deltas,
+            )
         })
-        .collect::<Vec<_>>()
-        .len();
-    let n_non_compliant_20 = vec
+        .collect::<Vec<_>>();
+
+    write_output(
+        &scenario.output.report,
+        &render_reports(&reports, scenario.output.format),
+    );
+}
+
+/// Whether every anchor lies on one line, which leaves ranges with a mirrored twin fix
+fn are_collinear(anchors: &[Point]) -> bool {
+    let Some(first) = anchors.first() else {
+        return true;
+    };
+    let Some(farthest) = anchors
         .iter()
-        .filter(|v| {
-            v.partial_cmp(&&(bounds.calc_max_span() * 0.20))
-                .unwrap()
-                .is_gt()
+        .max_by(|a, b| first.distance_to(a).total_cmp(&first.distance_to(b)))
+    else {
+        return true;
+    };
+
+    let direction = farthest - first;
+    let length = first.distance_to(farthest);
+
+    length == 0.0
+        || anchors.iter().all(|anchor| {
+            let offset = anchor - first;
+
+            (direction.x * offset.y - direction.y * offset.x).abs() <= 1e-9 * length.powi(2)
         })
-        .collect::<Vec<_>>()
-        .len();
-    println!();
-    println!(
-        "Non Compliant (5%): {} / {} ({:.2}%)",
-        n_non_compliant_5,
-        times,
-        (n_non_compliant_5 as f64 / times as f64) * 100.0
-    );
-    println!(
-        "Non Compliant (10%): {} / {} ({:.2}%)",
-        n_non_compliant_10,
-        times,
-        (n_non_compliant_10 as f64 / times as f64) * 100.0
-    );
-    println!(
-        "Non Compliant (20%): {} / {} ({:.2}%)",
-        n_non_compliant_20,
-        times,
-        (n_non_compliant_20 as f64 / times as f64) * 100.0
-    );
+}
 
-    fs::write(
-        "./out.csv",
-        deltas
-            // .filter(|v| ((v - mean) / std).abs() < 2.0)
-            .map(|v| (100.0 * &(v / bounds.calc_max_span())).to_string())
-            .collect::<Vec<String>>()
-            .join(","),
-    )
-    .expect("Bad file write");
+/// Checks that every one of `localizers` can solve the measurements of `mode`, the mode of the
+/// localizer named `measured_for`
+fn check_compare(
+    localizers: &[(&str, Localizer)],
+    measured_for: &str,
+    mode: &SimulationMode,
+) -> Result<(), String> {
+    match localizers
+        .iter()
+        .find(|(_, localizer)| !localizer.accepts_mode(mode))
+    {
+        Some((name, _)) => Err(format!(
+            "{} cannot solve the {} measurements taken for {}",
+            name,
+            mode.name(),
+            measured_for
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Exits with a clap validation error on `subcommand`
+fn validation_error(subcommand: &str, message: String) -> ! {
+    let mut command = Cli::command();
+    command.build();
+    command
+        .find_subcommand_mut(subcommand)
+        .expect("Bad subcommand name")
+        .error(ErrorKind::ValueValidation, message)
+        .exit()
+}
+
+/// Checks that the measurements on the command line fit `localizer` before solving
+fn check_solve(
+    localizer: &Localizer,
+    anchors: &[Point],
+    distances: &[f64],
+    bearings: &[f64],
+) -> Result<(), String> {
+    let n = anchors.len();
+
+    if n < localizer.min_anchors() {
+        return Err(format!(
+            "expected at least {} anchors, got {}",
+            localizer.min_anchors(),
+            n
+        ));
+    }
+
+    if !matches!(localizer, Localizer::Aoa(_)) && distances.len() != n {
+        return Err(format!(
+            "expected a --distance for each of the {} anchors, got {}",
+            n,
+            distances.len()
+        ));
+    }
+
+    if matches!(localizer, Localizer::Aoa(_) | Localizer::Hybrid(_)) && bearings.len() != n {
+        return Err(format!(
+            "expected a --bearing for each of the {} anchors, got {}",
+            n,
+            bearings.len()
+        ));
+    }
+
+    if matches!(localizer, Localizer::Range(_) | Localizer::Tdoa(_)) && are_collinear(anchors) {
+        return Err("expected anchors that do not all lie on one line".to_string());
+    }
+
+    Ok(())
+}
+
+fn solve(localizer: &str, anchors: &[Point], distances: &[f64], bearings: &[f64]) {
+    let localizer = Localizer::from_name(localizer).expect("Bad localizer name");
+
+    if let Err(err) = check_solve(&localizer, anchors, distances, bearings) {
+        validation_error("solve", err);
+    }
+
+    match localizer.solve(anchors, distances, bearings) {
+        Ok(pt) => println!("{}", pt),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    match Cli::parse().command {
+        Command::Simulate {
+            run,
+            times,
+            localizer,
+            trials_csv,
+            plots,
+            failures,
+            output,
+        } => simulate(
+            &run,
+            times,
+            &localizer,
+            &trials_csv,
+            &plots,
+            &failures,
+            &output,
+        ),
+        Command::Heatmap {
+            run,
+            grid,
+            localizer,
+            layout,
+            output,
+            svg,
+        } => heatmap(&run, &grid, &localizer, &layout, &output, &svg),
+        Command::Compare {
+            run,
+            times,
+            localizers,
+            output,
+        } => compare(&run, times, &localizers, &output),
+        Command::Sweep {
+            run,
+            times,
+            localizer,
+            error_margins,
+            anchor_counts,
+            sizes,
+            output,
+        } => sweep(
+            &run,
+            times,
+            &localizer,
+            &error_margins,
+            &anchor_counts,
+            &sizes,
+            &output,
+        ),
+        Command::Trial {
+            run,
+            localizer,
+            iteration,
+            output,
+        } => trial(&run, &localizer, iteration, &output),
+        Command::Replay {
+            file,
+            localizer,
+            scenes,
+            noise,
+        } => replay_failures(&file, &localizer, &scenes, &noise),
+        Command::Run { scenario } => run_scenario(&scenario),
+        Command::Solve {
+            localizer,
+            anchors,
+            distances,
+            bearings,
+        } => solve(&localizer, &anchors, &distances, &bearings),
+    }
 
     // let inaccuracy = 0.05;
     // let (lower_range, upper_range) = (100.0, 300.0);
@@ -615,3 +1383,117 @@ fn main() {
 
     // println!("{:?}", c);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise() -> NoiseArgs {
+        NoiseArgs {
+            error_margin: 0.05,
+            angle_std: 0.05,
+            clock_offset_std: 1.0,
+            rssi: false,
+            path_loss_exponent: 2.0,
+            reference_power: -59.0,
+            shadowing_std: 4.0,
+        }
+    }
+
+    fn named(names: &[&'static str]) -> Vec<(&'static str, Localizer)> {
+        names
+            .iter()
+            .map(|name| {
+                (
+                    *name,
+                    Localizer::from_name(name).expect("Bad localizer name"),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_below_one_are_rejected() {
+        assert!(parse_times("0").is_err());
+        assert!(parse_cells("0").is_err());
+        assert_eq!(parse_times("5"), Ok(5));
+    }
+
+    #[test]
+    fn layout_anchors_only_go_with_the_fixed_placement() {
+        let layout = |placement, anchor_pts: Vec<Point>| LayoutArgs {
+            placement,
+            anchor_pts,
+        };
+        let pts = vec![Point::new(0.0, 0.0), Point::new(10.0, 0.0)];
+
+        assert!(layout(PlacementKind::Fixed, pts[..1].to_vec())
+            .check()
+            .is_err());
+        assert!(layout(PlacementKind::Grid, pts.clone()).check().is_err());
+        assert_eq!(layout(PlacementKind::Fixed, pts).check(), Ok(()));
+        assert_eq!(layout(PlacementKind::Corners, vec![]).check(), Ok(()));
+    }
+
+    #[test]
+    fn comparison_rejects_range_localizers_on_tdoa_measurements() {
+        let runner = noise().runner(
+            "compare",
+            "chan_taylor",
+            3,
+            Rectangle::new(0.0, 0.0, 100.0, 100.0),
+        );
+
+        assert!(check_compare(
+            &named(&["chan_taylor", "paper_way"]),
+            "chan_taylor",
+            &runner.mode
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn comparison_accepts_tdoa_localizers_on_ranges() {
+        let runner = noise().runner(
+            "compare",
+            "paper_way",
+            3,
+            Rectangle::new(0.0, 0.0, 100.0, 100.0),
+        );
+
+        assert_eq!(
+            check_compare(
+                &named(&["paper_way", "chan_taylor"]),
+                "paper_way",
+                &runner.mode
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rssi_only_goes_with_range_localizers() {
+        let rssi = NoiseArgs {
+            rssi: true,
+            ..noise()
+        };
+        let runner = rssi.runner(
+            "simulate",
+            "huber_way",
+            3,
+            Rectangle::new(0.0, 0.0, 100.0, 100.0),
+        );
+
+        assert_eq!(runner.mode.name(), "rssi");
+        assert!(rssi.check("hybrid_way").is_err());
+        assert_eq!(noise().check("hybrid_way"), Ok(()));
+        assert_eq!(
+            check_compare(
+                &named(&["huber_way", "chan_taylor"]),
+                "huber_way",
+                &runner.mode
+            ),
+            Ok(())
+        );
+    }
+}
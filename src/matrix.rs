@@ -32,6 +32,35 @@ impl Matrix {
         }
     }
 
+    pub fn from_diagonal(diagonal: &[f64]) -> Self {
+        let size = diagonal.len();
+        let mut result = Matrix::new(size, size);
+
+        for (i, value) in diagonal.iter().enumerate() {
+            result.set(i, i, *value).unwrap();
+        }
+
+        result
+    }
+
+    pub fn identity(size: usize) -> Self {
+        Matrix::from_diagonal(&vec![1.0; size])
+    }
+
+    /// One line per row with the values separated by commas
+    pub fn to_csv(&self) -> String {
+        (0..self.n_rows)
+            .map(|row| {
+                self.get_row(row)
+                    .unwrap()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
     pub fn same_size_as(&self, mat: &Matrix) -> bool {
         self.n_rows == mat.n_rows && self.n_cols == mat.n_cols
     }
@@ -84,11 +113,11 @@ impl Matrix {
     }
 
     pub fn apply<F: FnMut(&f64)>(&self, mut func: F) {
-        self.data.iter().for_each(|n| func(n));
+        self.data.iter().for_each(&mut func);
     }
 
     pub fn apply_mut<F: FnMut(&mut f64)>(&mut self, mut func: F) {
-        self.data.iter_mut().for_each(|n| func(n));
+        self.data.iter_mut().for_each(&mut func);
     }
 
     pub fn dot(&self, rhs: &Matrix) -> Option<Matrix> {
@@ -112,10 +141,52 @@ impl Matrix {
         }
     }
 
+    /// Lower triangular `L` with `L * L^T == self`, `None` unless the matrix is symmetric
+    /// positive definite
+    pub fn cholesky(&self) -> Option<Matrix> {
+        if !self.is_square() {
+            return None;
+        }
+
+        let size = self.n_rows;
+        let mut result = Matrix::new(size, size);
+
+        for i in 0..size {
+            for j in 0..=i {
+                let sum = (0..j)
+                    .map(|k| result.get(i, k).unwrap() * result.get(j, k).unwrap())
+                    .sum::<f64>();
+
+                let value = if i == j {
+                    let diagonal = self.get(i, i).unwrap() - sum;
+
+                    if diagonal <= 0.0 {
+                        return None;
+                    }
+
+                    diagonal.sqrt()
+                } else {
+                    (self.get(i, j).unwrap() - sum) / result.get(j, j).unwrap()
+                };
+
+                result.set(i, j, value);
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Sum of the diagonal, `None` for non square matrices
+    pub fn trace(&self) -> Option<f64> {
+        if self.is_square() {
+            Some((0..self.n_rows).map(|i| self.get(i, i).unwrap()).sum())
+        } else {
+            None
+        }
+    }
+
     pub fn transpose(&self) -> Matrix {
-        let data = (0..self.n_rows)
-            .map(|row| self.get_row(row).unwrap().map(|v| *v))
-            .flatten();
+        let data = (0..self.n_cols).flat_map(|col| self.get_col(col).unwrap().copied());
         Matrix::from_iter(self.n_cols, self.n_rows, data)
     }
 
@@ -155,13 +226,12 @@ impl Matrix {
     // TODO: implement in less junky way
     pub fn invert(&self) -> Option<Matrix> {
         let matrix = (0..self.n_rows)
-            .map(|row| self.get_row(row).unwrap().map(|v| *v).collect::<Vec<_>>())
+            .map(|row| self.get_row(row).unwrap().copied().collect::<Vec<_>>())
             .collect::<Vec<_>>();
         let size = matrix.len();
 
         // Check if the matrix is square
         if size != matrix.iter().filter(|row| row.len() == size).count() {
-            println!("NOT SQUIRE");
             return None; // Matrix is not square
         }
 
@@ -187,19 +257,19 @@ impl Matrix {
             let pivot = augmented_matrix[col][col];
 
             if pivot == 0.0 {
-                println!("BAD PIVOT");
                 return None; // Matrix is not invertible
             }
 
-            for j in 0..(size * 2) {
-                augmented_matrix[col][j] /= pivot;
+            for value in augmented_matrix[col].iter_mut() {
+                *value /= pivot;
             }
 
-            for i in 0..size {
+            let normalized_row = augmented_matrix[col].clone();
+            for (i, row) in augmented_matrix.iter_mut().enumerate() {
                 if i != col {
-                    let factor = augmented_matrix[i][col];
-                    for j in 0..(size * 2) {
-                        augmented_matrix[i][j] -= factor * augmented_matrix[col][j];
+                    let factor = row[col];
+                    for (value, normalized) in row.iter_mut().zip(&normalized_row) {
+                        *value -= factor * normalized;
                     }
                 }
             }
@@ -215,7 +285,7 @@ impl Matrix {
         Some(Matrix::from_iter(
             self.n_rows,
             self.n_cols,
-            inverse.iter().map(|v| v.iter().map(|v| *v)).flatten(),
+            inverse.iter().flat_map(|v| v.iter().copied()),
         ))
         // let data = (0..self.n_rows)
         //     .map(|row| self.get_row(row).unwrap().map(|v| *v).collect::<Vec<_>>())
@@ -0,0 +1,35 @@
+use crate::{matrix::Matrix, point::Point};
+
+/// Linearised least squares position from ranges, subtracting the first anchor's circle equation
+/// from every other one. Needs at least 3 non collinear anchors.
+pub fn linear_least_squares(anchors: &[Point], distances: &[f64]) -> Option<Point> {
+    let n = anchors.len();
+
+    if n < 3 || distances.len() != n {
+        return None;
+    }
+
+    let mut a = Matrix::new(n - 1, 2);
+    let mut b = Matrix::new(n - 1, 1);
+
+    for i in 1..n {
+        a.set(i - 1, 0, anchors[i].x - anchors[0].x)?;
+        a.set(i - 1, 1, anchors[i].y - anchors[0].y)?;
+
+        b.set(
+            i - 1,
+            0,
+            distances[0].powi(2) - distances[i].powi(2) + anchors[i].pow(2).sum()
+                - anchors[0].pow(2).sum(),
+        )?;
+    }
+
+    let a_t = a.transpose();
+    let result = a_t.dot(&a)?.invert()?.dot(&a_t)?.dot(&(&b / 2.0))?;
+
+    Some(Point::new(*result.get(0, 0)?, *result.get(1, 0)?))
+}
+
+pub fn paper_way(anchors: &[Point], distances: &[f64]) -> Option<Point> {
+    linear_least_squares(anchors, distances)
+}
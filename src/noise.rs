@@ -0,0 +1,291 @@
+use std::fmt::{Debug, Display};
+
+use rand::{Rng, RngCore};
+use rand_distr::{Exp, Normal};
+
+/// Error model turning a true distance into a measured one, displayed as its name and parameters
+/// for reports
+pub trait NoiseModel: Debug + Display + Send + Sync {
+    /// Returns the measured distance, or `None` when the measurement is lost
+    fn apply(&self, dist: f64, rng: &mut dyn RngCore) -> Option<f64>;
+    /// Variance of the zero mean part of the error at `dist`, for weighting solvers and filters
+    fn variance(&self, dist: f64) -> f64;
+}
+
+/// Additive zero mean normal error
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Gaussian {
+    pub std: f64,
+}
+
+impl Gaussian {
+    pub fn new(std: f64) -> Self {
+        Gaussian { std }
+    }
+}
+
+impl Display for Gaussian {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Gaussian (std {})", self.std)
+    }
+}
+
+impl NoiseModel for Gaussian {
+    fn apply(&self, dist: f64, rng: &mut dyn RngCore) -> Option<f64> {
+        let noise = Normal::new(0.0, self.std).expect("Bad noise standard deviation");
+
+        Some(dist + rng.sample(noise))
+    }
+
+    fn variance(&self, _dist: f64) -> f64 {
+        self.std.powi(2)
+    }
+}
+
+/// `dist + dist * U(-margin, margin)`, the runner's original `error_margin` noise
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Multiplicative {
+    pub margin: f64,
+}
+
+impl Multiplicative {
+    pub fn new(margin: f64) -> Self {
+        Multiplicative { margin }
+    }
+}
+
+impl Display for Multiplicative {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Multiplicative (margin {})", self.margin)
+    }
+}
+
+impl NoiseModel for Multiplicative {
+    fn apply(&self, dist: f64, rng: &mut dyn RngCore) -> Option<f64> {
+        if self.margin == 0.0 {
+            return Some(dist);
+        }
+
+        Some(dist + dist * rng.gen_range(-self.margin..self.margin))
+    }
+
+    fn variance(&self, dist: f64) -> f64 {
+        (dist * self.margin).powi(2) / 3.0
+    }
+}
+
+/// Constant offset, such as an uncalibrated antenna delay
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Bias {
+    pub offset: f64,
+}
+
+impl Bias {
+    pub fn new(offset: f64) -> Self {
+        Bias { offset }
+    }
+}
+
+impl Display for Bias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bias (offset {})", self.offset)
+    }
+}
+
+impl NoiseModel for Bias {
+    fn apply(&self, dist: f64, _rng: &mut dyn RngCore) -> Option<f64> {
+        Some(dist + self.offset)
+    }
+
+    fn variance(&self, _dist: f64) -> f64 {
+        0.0
+    }
+}
+
+/// With `probability` the line of sight is blocked and the signal takes a longer path, adding an
+/// exponentially distributed bias averaging `mean_bias`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Nlos {
+    pub probability: f64,
+    pub mean_bias: f64,
+}
+
+impl Nlos {
+    pub fn new(probability: f64, mean_bias: f64) -> Self {
+        Nlos {
+            probability,
+            mean_bias,
+        }
+    }
+}
+
+impl Display for Nlos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "NLOS (probability {}, mean bias {})",
+            self.probability, self.mean_bias
+        )
+    }
+}
+
+impl NoiseModel for Nlos {
+    fn apply(&self, dist: f64, rng: &mut dyn RngCore) -> Option<f64> {
+        if !rng.gen_bool(self.probability) {
+            return Some(dist);
+        }
+
+        let bias = Exp::new(1.0 / self.mean_bias).expect("Bad NLOS mean bias");
+
+        Some(dist + rng.sample(bias))
+    }
+
+    fn variance(&self, _dist: f64) -> f64 {
+        self.probability * (2.0 - self.probability) * self.mean_bias.powi(2)
+    }
+}
+
+/// Rounds to the nearest multiple of `step`, like a ranging clock with a coarse tick
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Quantization {
+    pub step: f64,
+}
+
+impl Quantization {
+    pub fn new(step: f64) -> Self {
+        Quantization { step }
+    }
+}
+
+impl Display for Quantization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Quantization (step {})", self.step)
+    }
+}
+
+impl NoiseModel for Quantization {
+    fn apply(&self, dist: f64, _rng: &mut dyn RngCore) -> Option<f64> {
+        Some((dist / self.step).round() * self.step)
+    }
+
+    fn variance(&self, _dist: f64) -> f64 {
+        self.step.powi(2) / 12.0
+    }
+}
+
+/// Loses the measurement with `probability`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Dropout {
+    pub probability: f64,
+}
+
+impl Dropout {
+    pub fn new(probability: f64) -> Self {
+        Dropout { probability }
+    }
+}
+
+impl Display for Dropout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Dropout (probability {})", self.probability)
+    }
+}
+
+impl NoiseModel for Dropout {
+    fn apply(&self, dist: f64, rng: &mut dyn RngCore) -> Option<f64> {
+        if rng.gen_bool(self.probability) {
+            None
+        } else {
+            Some(dist)
+        }
+    }
+
+    fn variance(&self, _dist: f64) -> f64 {
+        0.0
+    }
+}
+
+/// Applies each model in turn to the output of the one before, stopping once one drops the
+/// measurement. Variances add up as if the errors were independent.
+#[derive(Debug, Default)]
+pub struct Stack {
+    pub models: Vec<Box<dyn NoiseModel>>,
+}
+
+impl Stack {
+    pub fn new(models: Vec<Box<dyn NoiseModel>>) -> Self {
+        Stack { models }
+    }
+}
+
+impl Display for Stack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let models_str = self
+            .models
+            .iter()
+            .map(|model| model.to_string())
+            .collect::<Vec<String>>()
+            .join(" + ");
+
+        write!(f, "{}", models_str)
+    }
+}
+
+impl NoiseModel for Stack {
+    fn apply(&self, dist: f64, rng: &mut dyn RngCore) -> Option<f64> {
+        self.models
+            .iter()
+            .try_fold(dist, |dist, model| model.apply(dist, rng))
+    }
+
+    fn variance(&self, dist: f64) -> f64 {
+        self.models.iter().map(|model| model.variance(dist)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn stack_applies_models_in_turn_and_adds_variances() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let stack = Stack::new(vec![
+            Box::new(Bias::new(2.0)),
+            Box::new(Quantization::new(5.0)),
+        ]);
+
+        assert_eq!(stack.apply(11.0, &mut rng), Some(15.0));
+
+        let stack = Stack::new(vec![
+            Box::new(Gaussian::new(2.0)),
+            Box::new(Multiplicative::new(0.3)),
+        ]);
+
+        assert!((stack.variance(10.0) - 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dropout_stops_the_stack() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let lost = Stack::new(vec![Box::new(Dropout::new(1.0)), Box::new(Bias::new(1.0))]);
+        let kept = Stack::new(vec![Box::new(Dropout::new(0.0)), Box::new(Bias::new(1.0))]);
+
+        assert_eq!(lost.apply(10.0, &mut rng), None);
+        assert_eq!(kept.apply(10.0, &mut rng), Some(11.0));
+    }
+
+    #[test]
+    fn nlos_only_lengthens_ranges() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let nlos = Nlos::new(1.0, 2.0);
+        let biases = (0..10_000)
+            .map(|_| nlos.apply(10.0, &mut rng).expect("Bad lost measurement") - 10.0)
+            .collect::<Vec<_>>();
+
+        assert!(biases.iter().all(|bias| *bias >= 0.0));
+        assert!((biases.iter().sum::<f64>() / biases.len() as f64 - 2.0).abs() < 0.1);
+    }
+}
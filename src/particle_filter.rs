@@ -0,0 +1,369 @@
+use std::{f64::consts::PI, ops::RangeInclusive};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Normal;
+
+use crate::{point::Point, rectangle::Rectangle, two_dim_shape::TwoDimShape};
+
+/// How particles are redrawn in proportion to their weights
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Resampling {
+    /// One random offset shared by evenly spaced pointers
+    Systematic,
+    /// An independent random offset inside every evenly spaced stratum
+    Stratified,
+}
+
+impl Resampling {
+    /// Returns the index of the particle each of the `weights.len()` new particles is copied from
+    pub fn resample(&self, weights: &[f64], rng: &mut impl Rng) -> Vec<usize> {
+        let n = weights.len();
+        let total = weights.iter().sum::<f64>();
+        let offset = rng.gen_range(0.0..1.0);
+
+        let mut indices = Vec::with_capacity(n);
+        let mut cumulative = weights[0] / total;
+        let mut index = 0;
+
+        for i in 0..n {
+            let pointer = match self {
+                Resampling::Systematic => (i as f64 + offset) / n as f64,
+                Resampling::Stratified => (i as f64 + rng.gen_range(0.0..1.0)) / n as f64,
+            };
+
+            while pointer > cumulative && index < n - 1 {
+                index += 1;
+                cumulative += weights[index] / total;
+            }
+
+            indices.push(index);
+        }
+
+        indices
+    }
+}
+
+/// Likelihood of a measured distance given the distance a particle predicts
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RangeLikelihood {
+    /// Zero mean normal error with a fixed standard deviation
+    Gaussian { std: f64 },
+    /// Zero mean normal error with a standard deviation proportional to the distance, like the
+    /// runner's `error_margin`
+    Relative { relative_std: f64 },
+    /// Normal error, plus with probability `nlos_probability` an exponentially distributed
+    /// positive bias averaging `mean_bias` from a blocked line of sight
+    Nlos {
+        std: f64,
+        nlos_probability: f64,
+        mean_bias: f64,
+    },
+}
+
+impl RangeLikelihood {
+    pub fn log_likelihood(&self, measured: f64, predicted: f64) -> f64 {
+        let normal =
+            |error: f64, std: f64| (-0.5 * (error / std).powi(2)).exp() / (std * (2.0 * PI).sqrt());
+        let error = measured - predicted;
+
+        let density = match *self {
+            RangeLikelihood::Gaussian { std } => normal(error, std),
+            RangeLikelihood::Relative { relative_std } => {
+                normal(error, (relative_std * predicted).max(f64::EPSILON))
+            }
+            RangeLikelihood::Nlos {
+                std,
+                nlos_probability,
+                mean_bias,
+            } => {
+                let bias = if error > 0.0 {
+                    (-error / mean_bias).exp() / mean_bias
+                } else {
+                    0.0
+                };
+
+                (1.0 - nlos_probability) * normal(error, std) + nlos_probability * bias
+            }
+        };
+
+        density.max(f64::MIN_POSITIVE).ln()
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Particle {
+    pub pt: Point,
+    /// Normalised, all weights of an estimate sum to 1
+    pub weight: f64,
+}
+
+/// Weighted particle cloud approximating the posterior
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParticleEstimate {
+    pub particles: Vec<Particle>,
+    /// Weighted mean of every particle, which falls between the modes of a multimodal posterior
+    pub mean: Point,
+    /// Weighted mean of the particles within the initial particle spacing of the heaviest one
+    pub mode: Point,
+}
+
+/// Static particle filter localizer.
+///
+/// Particles are spread uniformly over the prior region, then repeatedly weighted by the range
+/// likelihood, resampled and jittered with a shrinking amount of noise so they settle into the
+/// modes of the posterior.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParticleFilter {
+    /// Has to be at least 1, [`ParticleFilter::localize`] gives no estimate without particles
+    pub particle_count: usize,
+    pub iterations: usize,
+    pub resampling: Resampling,
+    pub likelihood: RangeLikelihood,
+    /// Seeds the filter's own generator, so the same input always gives the same estimate
+    pub seed: u64,
+}
+
+impl ParticleFilter {
+    /// Panics when `particle_count` is 0
+    pub fn new(particle_count: usize, resampling: Resampling, likelihood: RangeLikelihood) -> Self {
+        assert!(particle_count > 0, "Bad particle count, needs at least 1");
+
+        ParticleFilter {
+            particle_count,
+            iterations: 5,
+            resampling,
+            likelihood,
+            seed: 0,
+        }
+    }
+
+    /// Region every tag consistent with the measurements lies in, the anchors' bounding box grown
+    /// by the largest distance
+    pub fn prior_bounds(anchors: &[Point], distances: &[f64]) -> Rectangle {
+        let margin = distances.iter().copied().fold(0.0, f64::max);
+        let (min_x, max_x, min_y, max_y) = anchors.iter().fold(
+            (
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(min_x, max_x, min_y, max_y), pt| {
+                (
+                    min_x.min(pt.x),
+                    max_x.max(pt.x),
+                    min_y.min(pt.y),
+                    max_y.max(pt.y),
+                )
+            },
+        );
+
+        Rectangle::new(
+            min_x - margin,
+            min_y - margin,
+            max_x - min_x + 2.0 * margin,
+            max_y - min_y + 2.0 * margin,
+        )
+    }
+
+    fn weigh(&self, anchors: &[Point], distances: &[f64], pts: &[Point]) -> Vec<f64> {
+        let log_weights = pts
+            .iter()
+            .map(|pt| {
+                anchors
+                    .iter()
+                    .zip(distances)
+                    .map(|(anchor, dist)| {
+                        self.likelihood
+                            .log_likelihood(*dist, anchor.distance_to(pt))
+                    })
+                    .sum::<f64>()
+            })
+            .collect::<Vec<_>>();
+
+        // Shift by the largest log weight so the exponentials cannot all underflow
+        let max = log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let weights = log_weights
+            .iter()
+            .map(|w| (w - max).exp())
+            .collect::<Vec<_>>();
+        let total = weights.iter().sum::<f64>();
+
+        weights.iter().map(|w| w / total).collect()
+    }
+
+    /// Estimate from `distances` to `anchors` with the particles drawn over `prior`, `None`
+    /// without particles or a finite prior region to draw them from
+    pub fn localize(
+        &self,
+        anchors: &[Point],
+        distances: &[f64],
+        prior: &Rectangle,
+    ) -> Option<ParticleEstimate> {
+        let finite = |range: RangeInclusive<f64>| {
+            range.start().is_finite() && range.end().is_finite() && !range.is_empty()
+        };
+
+        if self.particle_count == 0 || !finite(prior.x_range()) || !finite(prior.y_range()) {
+            return None;
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        let mut pts = (0..self.particle_count)
+            .map(|_| {
+                Point::new(
+                    rng.gen_range(prior.x_range()),
+                    rng.gen_range(prior.y_range()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut weights = self.weigh(anchors, distances, &pts);
+
+        // Jitter starts at about the spacing of the initial particles and halves every iteration
+        let spacing = prior.calc_max_span() / (self.particle_count as f64).sqrt();
+        let mut jitter_std = spacing;
+
+        for _ in 0..self.iterations {
+            let jitter = Normal::new(0.0, jitter_std).ok()?;
+
+            pts = self
+                .resampling
+                .resample(&weights, &mut rng)
+                .iter()
+                .map(|i| &pts[*i] + &Point::new(rng.sample(jitter), rng.sample(jitter)))
+                .collect();
+            weights = self.weigh(anchors, distances, &pts);
+
+            jitter_std /= 2.0;
+        }
+
+        let weighted_mean = |particles: &mut dyn Iterator<Item = (&Point, &f64)>| {
+            let (sum, total) = particles
+                .fold((Point::new(0.0, 0.0), 0.0), |(sum, total), (pt, w)| {
+                    (&sum + &(pt * *w), total + w)
+                });
+
+            &sum / total
+        };
+
+        let heaviest = pts
+            .iter()
+            .zip(&weights)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(pt, _)| pt.clone())?;
+
+        let mean = weighted_mean(&mut pts.iter().zip(&weights));
+        let mode = weighted_mean(
+            &mut pts
+                .iter()
+                .zip(&weights)
+                .filter(|(pt, _)| pt.distance_to(&heaviest) <= spacing),
+        );
+
+        Some(ParticleEstimate {
+            particles: pts
+                .into_iter()
+                .zip(weights)
+                .map(|(pt, weight)| Particle { pt, weight })
+                .collect(),
+            mean,
+            mode,
+        })
+    }
+}
+
+impl Default for ParticleFilter {
+    fn default() -> Self {
+        ParticleFilter::new(
+            2000,
+            Resampling::Systematic,
+            RangeLikelihood::Relative { relative_std: 0.03 },
+        )
+    }
+}
+
+/// Mode of the default particle filter over the measurements' prior region, which needs at least
+/// one anchor
+pub fn particle_way(anchors: &[Point], distances: &[f64]) -> Option<Point> {
+    if anchors.is_empty() || distances.len() != anchors.len() {
+        return None;
+    }
+
+    ParticleFilter::default()
+        .localize(
+            anchors,
+            distances,
+            &ParticleFilter::prior_bounds(anchors, distances),
+        )
+        .map(|estimate| estimate.mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchors() -> Vec<Point> {
+        vec![
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(0.0, 100.0),
+        ]
+    }
+
+    #[test]
+    fn particles_settle_on_the_tag() {
+        let tag = Point::new(30.0, 60.0);
+        let anchors = anchors();
+        let distances = anchors
+            .iter()
+            .map(|anchor| anchor.distance_to(&tag))
+            .collect::<Vec<_>>();
+
+        let filter = ParticleFilter::new(
+            1000,
+            Resampling::Stratified,
+            RangeLikelihood::Gaussian { std: 1.0 },
+        );
+        let estimate = filter
+            .localize(
+                &anchors,
+                &distances,
+                &ParticleFilter::prior_bounds(&anchors, &distances),
+            )
+            .expect("Bad missing estimate");
+
+        assert!(estimate.mode.distance_to(&tag) < 2.0, "{}", estimate.mode);
+        assert_eq!(estimate.particles.len(), 1000);
+        assert!((estimate.particles.iter().map(|p| p.weight).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert_eq!(particle_way(&anchors, &distances[..2]), None);
+    }
+
+    #[test]
+    fn no_particles_or_prior_region_has_no_estimate() {
+        let mut filter = ParticleFilter::default();
+        let distances = [50.0, 50.0, 50.0];
+
+        assert_eq!(
+            filter.localize(
+                &anchors(),
+                &distances,
+                &Rectangle::new(f64::NAN, 0.0, 10.0, 10.0)
+            ),
+            None
+        );
+
+        filter.particle_count = 0;
+        assert_eq!(
+            filter.localize(
+                &anchors(),
+                &distances,
+                &Rectangle::new(0.0, 0.0, 10.0, 10.0)
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use rand::Rng;
+use rand_distr::Normal;
+
+use crate::{
+    noise::NoiseModel,
+    point::Point,
+    rectangle::Rectangle,
+    two_dim_shape::TwoDimShape,
+    uncertainty::{self, Dop},
+};
+
+/// Centres of the cells of the most square grid over `bounds` with at least `count` cells, row
+/// by row from the origin
+fn grid(bounds: &Rectangle, count: usize) -> Vec<Point> {
+    let aspect = bounds.width / bounds.height;
+    let cols = ((count as f64 * aspect).sqrt().round() as usize).clamp(1, count.max(1));
+    let rows = count.div_ceil(cols);
+    let cell = Point::new(bounds.width / cols as f64, bounds.height / rows as f64);
+
+    (0..count)
+        .map(|i| {
+            let index = Point::new((i % cols) as f64 + 0.5, (i / cols) as f64 + 0.5);
+
+            &bounds.pt + &(&index * &cell)
+        })
+        .collect()
+}
+
+/// Where the anchors go inside the bounds
+#[derive(PartialEq, Debug, Clone)]
+pub enum AnchorPlacement {
+    /// Uniformly at random, redrawn every trial
+    Random,
+    /// The corners of the bounds, with any anchors past the fourth shared out between the edges
+    /// and evenly spaced along them
+    Corners,
+    /// Evenly spaced along the edges, starting at the bounds' origin
+    Perimeter,
+    /// Centres of the cells of the most square grid with enough cells
+    Grid,
+    /// Exactly these anchors, whatever the anchor count
+    Fixed(Vec<Point>),
+}
+
+impl AnchorPlacement {
+    /// Point `distance` along the perimeter of `bounds`, counter-clockwise from its origin
+    fn along_perimeter(bounds: &Rectangle, distance: f64) -> Point {
+        let (w, h) = (bounds.width, bounds.height);
+        let d = distance.rem_euclid(2.0 * (w + h));
+
+        let offset = if d < w {
+            Point::new(d, 0.0)
+        } else if d < w + h {
+            Point::new(w, d - w)
+        } else if d < 2.0 * w + h {
+            Point::new(w - (d - w - h), h)
+        } else {
+            Point::new(0.0, h - (d - 2.0 * w - h))
+        };
+
+        &bounds.pt + &offset
+    }
+
+    pub fn place(&self, bounds: &Rectangle, count: usize, rng: &mut impl Rng) -> Vec<Point> {
+        let perimeter = 2.0 * (bounds.width + bounds.height);
+
+        match self {
+            AnchorPlacement::Random => (0..count)
+                .map(|_| {
+                    Point::new(
+                        rng.gen_range(bounds.x_range()),
+                        rng.gen_range(bounds.y_range()),
+                    )
+                })
+                .collect(),
+            AnchorPlacement::Corners => {
+                // Opposite corners first, so any three of them span the bounds
+                let [bottom_left, bottom_right, top_left, top_right] = bounds.get_corners();
+                let extra = count.saturating_sub(4);
+                let (w, h) = (bounds.width, bounds.height);
+                // Start and length of every edge along the perimeter
+                let edges = [(0.0, w), (w, h), (w + h, w), (2.0 * w + h, h)];
+
+                [bottom_left, top_right, bottom_right, top_left]
+                    .into_iter()
+                    .take(count)
+                    .chain((0..extra).map(|i| {
+                        let (start, length) = edges[i % 4];
+                        let on_edge = (extra - i % 4).div_ceil(4);
+
+                        Self::along_perimeter(
+                            bounds,
+                            start + length * (i / 4 + 1) as f64 / (on_edge + 1) as f64,
+                        )
+                    }))
+                    .collect()
+            }
+            AnchorPlacement::Perimeter => (0..count)
+                .map(|i| Self::along_perimeter(bounds, i as f64 * perimeter / count as f64))
+                .collect(),
+            AnchorPlacement::Grid => grid(bounds, count),
+            AnchorPlacement::Fixed(anchors) => anchors.clone(),
+        }
+    }
+}
+
+/// Anchor layout with its accuracy averaged over the region
+#[derive(PartialEq, Debug, Clone)]
+pub struct Layout {
+    pub anchors: Vec<Point>,
+    pub mean_gdop: f64,
+    /// Mean DRMS of a least squares fix under the noise model the layout was evaluated with, over
+    /// the tag positions where the geometry allows a fix at all
+    pub expected_error: f64,
+}
+
+/// Hill climbing search for the anchor positions with the lowest mean GDOP over the bounds
+#[derive(PartialEq, Debug, Clone)]
+pub struct LayoutOptimizer {
+    /// Tag positions on a `resolution x resolution` grid the GDOP is averaged over, at least 1
+    pub resolution: usize,
+    pub iterations: usize,
+    /// Cap on the GDOP at a single tag position, so a tag right at an anchor or on the line
+    /// through two anchors does not make the mean infinite
+    pub max_gdop: f64,
+}
+
+impl LayoutOptimizer {
+    /// Panics when `resolution` is 0
+    pub fn new(resolution: usize, iterations: usize) -> Self {
+        assert!(resolution > 0, "Bad resolution, needs at least 1");
+
+        LayoutOptimizer {
+            resolution,
+            iterations,
+            max_gdop: 100.0,
+        }
+    }
+
+    /// Panics when `resolution` is 0, as there would be nothing to average over
+    fn samples(&self, bounds: &Rectangle) -> Vec<Point> {
+        assert!(self.resolution > 0, "Bad resolution, needs at least 1");
+
+        grid(bounds, self.resolution.pow(2))
+    }
+
+    fn mean_gdop(&self, anchors: &[Point], samples: &[Point]) -> f64 {
+        samples
+            .iter()
+            .map(|pt| Dop::new(anchors, pt).gdop.min(self.max_gdop))
+            .sum::<f64>()
+            / samples.len() as f64
+    }
+
+    /// Mean GDOP and expected error of `anchors` over the bounds. Panics when `resolution` is 0.
+    pub fn evaluate(
+        &self,
+        anchors: &[Point],
+        bounds: &Rectangle,
+        noise: &dyn NoiseModel,
+    ) -> Layout {
+        let samples = self.samples(bounds);
+        let errors = samples
+            .iter()
+            .filter_map(|pt| {
+                let variances = anchors
+                    .iter()
+                    .map(|anchor| noise.variance(anchor.distance_to(pt)))
+                    .collect::<Vec<_>>();
+
+                uncertainty::diagonal_covariance(
+                    &uncertainty::range_jacobian(anchors, pt),
+                    &variances,
+                )?
+                .trace()
+                .map(f64::sqrt)
+                .filter(|drms| drms.is_finite())
+            })
+            .collect::<Vec<_>>();
+
+        Layout {
+            anchors: anchors.to_vec(),
+            mean_gdop: self.mean_gdop(anchors, &samples),
+            expected_error: errors.iter().sum::<f64>() / errors.len().max(1) as f64,
+        }
+    }
+
+    /// Starts from the best of the placement strategies, then moves one anchor at a time by a
+    /// shrinking random step, keeping the moves that lower the mean GDOP. Panics when `resolution`
+    /// is 0.
+    pub fn optimize(
+        &self,
+        bounds: &Rectangle,
+        count: usize,
+        noise: &dyn NoiseModel,
+        rng: &mut impl Rng,
+    ) -> Layout {
+        let samples = self.samples(bounds);
+
+        let (mut anchors, mut best) = [
+            AnchorPlacement::Corners,
+            AnchorPlacement::Perimeter,
+            AnchorPlacement::Grid,
+            AnchorPlacement::Random,
+        ]
+        .iter()
+        .map(|placement| {
+            let anchors = placement.place(bounds, count, rng);
+            let gdop = self.mean_gdop(&anchors, &samples);
+
+            (anchors, gdop)
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+
+        let span = bounds.calc_max_span();
+
+        for iteration in 0..self.iterations {
+            if anchors.is_empty() {
+                break;
+            }
+
+            // From a tenth of the bounds down to a thousandth
+            let progress = iteration as f64 / self.iterations as f64;
+            let step = Normal::new(0.0, span * (0.1 * (1.0 - progress) + 0.001)).unwrap();
+
+            let index = rng.gen_range(0..anchors.len());
+            let moved = &anchors[index] + &Point::new(rng.sample(step), rng.sample(step));
+            let previous = std::mem::replace(
+                &mut anchors[index],
+                Point::new(
+                    moved.x.clamp(bounds.pt.x, bounds.pt.x + bounds.width),
+                    moved.y.clamp(bounds.pt.y, bounds.pt.y + bounds.height),
+                ),
+            );
+
+            let gdop = self.mean_gdop(&anchors, &samples);
+
+            if gdop < best {
+                best = gdop;
+            } else {
+                anchors[index] = previous;
+            }
+        }
+
+        self.evaluate(&anchors, bounds, noise)
+    }
+}
+
+impl Default for LayoutOptimizer {
+    fn default() -> Self {
+        LayoutOptimizer::new(10, 500)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::noise::Gaussian;
+
+    use super::*;
+
+    fn bounds() -> Rectangle {
+        Rectangle::new(0.0, 0.0, 100.0, 50.0)
+    }
+
+    #[test]
+    fn strategies_place_the_anchors_inside_the_bounds() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(
+            AnchorPlacement::Corners.place(&bounds(), 4, &mut rng),
+            vec![
+                Point::new(0.0, 0.0),
+                Point::new(100.0, 50.0),
+                Point::new(100.0, 0.0),
+                Point::new(0.0, 50.0),
+            ]
+        );
+        assert_eq!(
+            AnchorPlacement::Perimeter.place(&bounds(), 3, &mut rng),
+            vec![
+                Point::new(0.0, 0.0),
+                Point::new(100.0, 0.0),
+                Point::new(50.0, 50.0),
+            ]
+        );
+        assert_eq!(
+            AnchorPlacement::Grid.place(&bounds(), 2, &mut rng),
+            vec![Point::new(25.0, 25.0), Point::new(75.0, 25.0)]
+        );
+
+        for placement in [
+            AnchorPlacement::Random,
+            AnchorPlacement::Corners,
+            AnchorPlacement::Perimeter,
+            AnchorPlacement::Grid,
+        ] {
+            let anchors = placement.place(&bounds(), 7, &mut rng);
+
+            assert_eq!(anchors.len(), 7);
+            assert!(anchors.iter().all(|anchor| {
+                bounds().x_range().contains(&anchor.x) && bounds().y_range().contains(&anchor.y)
+            }));
+        }
+    }
+
+    #[test]
+    fn optimizer_does_not_do_worse_than_the_strategies() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let optimizer = LayoutOptimizer::new(5, 200);
+        let noise = Gaussian::new(1.0);
+
+        let corners = optimizer.evaluate(
+            &AnchorPlacement::Corners.place(&bounds(), 4, &mut rng),
+            &bounds(),
+            &noise,
+        );
+        let optimized = optimizer.optimize(&bounds(), 4, &noise, &mut rng);
+
+        assert_eq!(optimized.anchors.len(), 4);
+        assert!(optimized.mean_gdop <= corners.mean_gdop);
+        assert!(optimized.expected_error.is_finite() && optimized.expected_error > 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bad resolution")]
+    fn optimizer_without_samples_is_rejected() {
+        let optimizer = LayoutOptimizer {
+            resolution: 0,
+            ..LayoutOptimizer::default()
+        };
+
+        optimizer.evaluate(&[Point::new(0.0, 0.0)], &bounds(), &Gaussian::new(1.0));
+    }
+}
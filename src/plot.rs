@@ -0,0 +1,432 @@
+use crate::{
+    circle::Circle, matrix::Matrix, point::Point, rectangle::Rectangle, stats,
+    test_runner::TestResult,
+};
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 480.0;
+/// Room left around the plot area for the title, ticks and labels
+const MARGIN: f64 = 60.0;
+
+/// Median absolute deviations above the median past which the histogram stops, as main.py did
+const HISTOGRAM_CUTOFF: f64 = 20.0;
+
+/// Colours of the heatmap scale, from the lowest value to the highest
+const HEATMAP_COLORS: [(f64, f64, f64); 5] = [
+    (68.0, 1.0, 84.0),
+    (59.0, 82.0, 139.0),
+    (33.0, 145.0, 140.0),
+    (94.0, 201.0, 98.0),
+    (253.0, 231.0, 37.0),
+];
+
+/// Roughly five round values between `min` and `max`
+fn ticks(min: f64, max: f64) -> Vec<f64> {
+    let span = max - min;
+
+    if !(span > 0.0 && span.is_finite()) {
+        return vec![min];
+    }
+
+    let magnitude = 10f64.powf((span / 5.0).log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| span / step <= 6.0)
+        .unwrap_or(10.0 * magnitude);
+
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+fn format_tick(value: f64) -> String {
+    let rounded = (value * 1e6).round() / 1e6;
+
+    format!("{}", rounded)
+}
+
+/// Escapes the characters with a meaning in SVG text
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Maps data coordinates onto the plot area, with `y` pointing up
+#[derive(Debug, Clone, Copy)]
+struct Axes {
+    x_min: f64,
+    x_max: f64,
+    y_min: f64,
+    y_max: f64,
+}
+
+impl Axes {
+    fn new(x_min: f64, x_max: f64, y_min: f64, y_max: f64) -> Self {
+        // Keeps a flat range from dividing by zero
+        let widen = |min: f64, max: f64| {
+            if max > min {
+                (min, max)
+            } else {
+                (min, min + 1.0)
+            }
+        };
+        let (x_min, x_max) = widen(x_min, x_max);
+        let (y_min, y_max) = widen(y_min, y_max);
+
+        Axes {
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+        }
+    }
+
+    fn x(&self, x: f64) -> f64 {
+        MARGIN + (x - self.x_min) / (self.x_max - self.x_min) * (WIDTH - 2.0 * MARGIN)
+    }
+
+    fn y(&self, y: f64) -> f64 {
+        HEIGHT - MARGIN - (y - self.y_min) / (self.y_max - self.y_min) * (HEIGHT - 2.0 * MARGIN)
+    }
+
+    /// Scales a distance along the x axis
+    fn length(&self, length: f64) -> f64 {
+        length / (self.x_max - self.x_min) * (WIDTH - 2.0 * MARGIN)
+    }
+
+    /// Title, axis lines, ticks and labels
+    fn frame(&self, title: &str, x_label: &str, y_label: &str) -> Vec<String> {
+        let (left, right) = (MARGIN, WIDTH - MARGIN);
+        let (top, bottom) = (MARGIN, HEIGHT - MARGIN);
+
+        let mut elements = vec![
+            format!(
+                r#"<text x="{}" y="{}" text-anchor="middle" font-size="16">{}</text>"#,
+                WIDTH / 2.0,
+                MARGIN / 2.0,
+                escape(title)
+            ),
+            format!(
+                r#"<path d="M {left} {top} L {left} {bottom} L {right} {bottom}" fill="none" stroke="black"/>"#
+            ),
+            format!(
+                r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+                WIDTH / 2.0,
+                HEIGHT - 15.0,
+                escape(x_label)
+            ),
+            format!(
+                r#"<text x="15" y="{}" text-anchor="middle" transform="rotate(-90 15 {})">{}</text>"#,
+                HEIGHT / 2.0,
+                HEIGHT / 2.0,
+                escape(y_label)
+            ),
+        ];
+
+        for tick in ticks(self.x_min, self.x_max) {
+            let x = self.x(tick);
+
+            elements.push(format!(
+                r#"<line x1="{x}" y1="{bottom}" x2="{x}" y2="{}" stroke="black"/><text x="{x}" y="{}" text-anchor="middle" font-size="11">{}</text>"#,
+                bottom + 5.0,
+                bottom + 18.0,
+                format_tick(tick)
+            ));
+        }
+
+        for tick in ticks(self.y_min, self.y_max) {
+            let y = self.y(tick);
+
+            elements.push(format!(
+                r#"<line x1="{}" y1="{y}" x2="{left}" y2="{y}" stroke="black"/><text x="{}" y="{}" text-anchor="end" font-size="11">{}</text>"#,
+                left - 5.0,
+                left - 8.0,
+                y + 4.0,
+                format_tick(tick)
+            ));
+        }
+
+        elements
+    }
+
+    /// Clips whatever uses `clip-path="url(#plot-area)"` to the plot area
+    fn clip(&self) -> String {
+        format!(
+            r#"<clipPath id="plot-area"><rect x="{}" y="{}" width="{}" height="{}"/></clipPath>"#,
+            MARGIN,
+            MARGIN,
+            WIDTH - 2.0 * MARGIN,
+            HEIGHT - 2.0 * MARGIN
+        )
+    }
+}
+
+/// Whole SVG document around `elements`
+fn document(elements: Vec<String>) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" viewBox=\"0 0 {WIDTH} {HEIGHT}\" font-family=\"sans-serif\">\n<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n{}\n</svg>\n",
+        elements.join("\n")
+    )
+}
+
+/// Histogram of the deltas in `bins` bins, ignoring failed trials and the far outliers main.py
+/// used to reject
+pub fn histogram(deltas: &[f64], bins: usize) -> String {
+    let deltas = deltas
+        .iter()
+        .copied()
+        .filter(|delta| !delta.is_nan())
+        .collect::<Vec<_>>();
+    let acc = deltas.iter().copied().collect::<stats::Accumulator>();
+    let (min, max) = (acc.min().unwrap_or(0.0), acc.max().unwrap_or(1.0));
+
+    let cutoff = match (stats::median(&deltas), stats::mad(&deltas)) {
+        (Some(median), Some(mad)) if mad > 0.0 => (median + HISTOGRAM_CUTOFF * mad).min(max),
+        _ => max,
+    };
+
+    let mut histogram = stats::Histogram::new(bins, min, cutoff);
+    deltas.iter().for_each(|delta| histogram.push(*delta));
+
+    let highest = histogram.counts.iter().copied().max().unwrap_or(0);
+    let axes = Axes::new(min, cutoff, 0.0, highest as f64);
+
+    let title = match histogram.outside {
+        0 => "Histogram of |predicted - actual|".to_string(),
+        outside => format!(
+            "Histogram of |predicted - actual| ({} outliers left out)",
+            outside
+        ),
+    };
+    let mut elements = axes.frame(&title, "Delta", "Number of Times (#)");
+
+    for ((lower, upper), count) in histogram.bin_edges().into_iter().zip(&histogram.counts) {
+        let top = axes.y(*count as f64);
+
+        elements.push(format!(
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#1f77b4" stroke="white" stroke-width="0.5"/>"##,
+            axes.x(lower),
+            top,
+            axes.x(upper) - axes.x(lower),
+            axes.y(0.0) - top
+        ));
+    }
+
+    document(elements)
+}
+
+/// Empirical CDF of the deltas up to their 99th percentile, ignoring failed trials
+pub fn cdf(deltas: &[f64]) -> String {
+    let probabilities = (0..=198).map(|i| i as f64 / 200.0).collect::<Vec<_>>();
+    let quantiles = stats::quantiles(deltas, &probabilities).unwrap_or_default();
+
+    let axes = Axes::new(
+        quantiles.first().copied().unwrap_or(0.0),
+        quantiles.last().copied().unwrap_or(1.0),
+        0.0,
+        1.0,
+    );
+    let mut elements = axes.frame("CDF of |predicted - actual|", "Delta", "Fraction of Trials");
+
+    let points = quantiles
+        .iter()
+        .zip(&probabilities)
+        .map(|(delta, probability)| format!("{},{}", axes.x(*delta), axes.y(*probability)))
+        .collect::<Vec<_>>();
+
+    elements.push(format!(
+        r##"<polyline points="{}" fill="none" stroke="#1f77b4" stroke-width="2"/>"##,
+        points.join(" ")
+    ));
+
+    document(elements)
+}
+
+/// True and predicted positions of every trial, joined by a line, over the anchors and the range
+/// circles of the anchors that were not dropped. Anything outside `bounds`, padded by a tenth, is
+/// cut off, so keep `results` to a handful of trials for the circles to stay readable.
+pub fn scatter(results: &[TestResult], bounds: &Rectangle) -> String {
+    let (pad_x, pad_y) = (bounds.width / 10.0, bounds.height / 10.0);
+    let axes = Axes::new(
+        bounds.pt.x - pad_x,
+        bounds.pt.x + bounds.width + pad_x,
+        bounds.pt.y - pad_y,
+        bounds.pt.y + bounds.height + pad_y,
+    );
+
+    let mut elements = axes.frame("True vs Predicted Positions", "x", "y");
+    elements.push(axes.clip());
+
+    let (mut circles, mut lines, mut anchors, mut tags, mut predictions) =
+        (vec![], vec![], vec![], vec![], vec![]);
+    let dot = |pt: &Point, color: &str| {
+        format!(
+            r#"<circle cx="{}" cy="{}" r="3" fill="{}"/>"#,
+            axes.x(pt.x),
+            axes.y(pt.y),
+            color
+        )
+    };
+
+    for result in results {
+        let (received_pts, distances) = result.received();
+
+        for (anchor, distance) in received_pts.iter().zip(&distances) {
+            let range = Circle::new(anchor.x, anchor.y, *distance);
+
+            circles.push(format!(
+                r##"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="#7f7f7f" stroke-opacity="0.3"/>"##,
+                axes.x(range.pt.x),
+                axes.y(range.pt.y),
+                axes.length(range.radius).abs()
+            ));
+        }
+
+        for anchor in &result.anchor_pts {
+            anchors.push(format!(
+                r#"<rect x="{}" y="{}" width="8" height="8" fill="black"/>"#,
+                axes.x(anchor.x) - 4.0,
+                axes.y(anchor.y) - 4.0
+            ));
+        }
+
+        if result.predicted_pt.x.is_finite() && result.predicted_pt.y.is_finite() {
+            lines.push(format!(
+                r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#d62728" stroke-opacity="0.5"/>"##,
+                axes.x(result.tag_pt.x),
+                axes.y(result.tag_pt.y),
+                axes.x(result.predicted_pt.x),
+                axes.y(result.predicted_pt.y)
+            ));
+            predictions.push(dot(&result.predicted_pt, "#d62728"));
+        }
+
+        tags.push(dot(&result.tag_pt, "#2ca02c"));
+    }
+
+    elements.push(format!(
+        r#"<g clip-path="url(#plot-area)">{}</g>"#,
+        [circles, lines, anchors, tags, predictions]
+            .concat()
+            .join("")
+    ));
+
+    // Legend along the top right of the plot area
+    for (i, (label, color)) in [
+        ("Anchor", "black"),
+        ("True", "#2ca02c"),
+        ("Predicted", "#d62728"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let y = MARGIN + 15.0 + i as f64 * 18.0;
+
+        elements.push(format!(
+            r#"<circle cx="{}" cy="{}" r="4" fill="{}"/><text x="{}" y="{}" font-size="12">{}</text>"#,
+            WIDTH - MARGIN - 80.0,
+            y,
+            color,
+            WIDTH - MARGIN - 70.0,
+            y + 4.0,
+            label
+        ));
+    }
+
+    document(elements)
+}
+
+/// Colour of `fraction` of the way along the heatmap scale
+fn heatmap_color(fraction: f64) -> String {
+    let position = fraction.clamp(0.0, 1.0) * (HEATMAP_COLORS.len() - 1) as f64;
+    let index = (position.floor() as usize).min(HEATMAP_COLORS.len() - 2);
+    let t = position - index as f64;
+
+    let (from, to) = (HEATMAP_COLORS[index], HEATMAP_COLORS[index + 1]);
+    let mix = |a: f64, b: f64| (a + (b - a) * t).round() as u8;
+
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        mix(from.0, to.0),
+        mix(from.1, to.1),
+        mix(from.2, to.2)
+    )
+}
+
+/// `values` of a grid over `bounds`, such as one of the matrices of a
+/// [`crate::heatmap::Heatmap`], row `0` along the bottom edge. Cells without a value are left
+/// blank and the scale runs from the lowest value to the highest.
+pub fn heatmap(bounds: &Rectangle, values: &Matrix, title: &str) -> String {
+    let (rows, cols) = (values.n_rows, values.n_cols);
+    let acc = (0..rows)
+        .flat_map(|row| (0..cols).map(move |col| (row, col)))
+        .filter_map(|(row, col)| values.get(row, col).copied())
+        .filter(|value| value.is_finite())
+        .collect::<stats::Accumulator>();
+    let (min, max) = (acc.min().unwrap_or(0.0), acc.max().unwrap_or(0.0));
+
+    let axes = Axes::new(
+        bounds.pt.x,
+        bounds.pt.x + bounds.width,
+        bounds.pt.y,
+        bounds.pt.y + bounds.height,
+    );
+    let mut elements = axes.frame(title, "x", "y");
+
+    let (cell_width, cell_height) = (bounds.width / cols as f64, bounds.height / rows as f64);
+
+    for row in 0..rows {
+        for col in 0..cols {
+            let value = match values.get(row, col) {
+                Some(value) if value.is_finite() => *value,
+                _ => continue,
+            };
+            let (x, y) = (
+                bounds.pt.x + col as f64 * cell_width,
+                bounds.pt.y + row as f64 * cell_height,
+            );
+            let fraction = if max > min {
+                (value - min) / (max - min)
+            } else {
+                0.0
+            };
+
+            elements.push(format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"><title>{}</title></rect>"#,
+                axes.x(x),
+                axes.y(y + cell_height),
+                axes.x(x + cell_width) - axes.x(x),
+                axes.y(y) - axes.y(y + cell_height),
+                heatmap_color(fraction),
+                format_tick(value)
+            ));
+        }
+    }
+
+    // Colour bar to the right of the plot area
+    let (bar_x, bar_height) = (WIDTH - MARGIN + 10.0, HEIGHT - 2.0 * MARGIN);
+
+    for i in 0..50 {
+        elements.push(format!(
+            r#"<rect x="{}" y="{}" width="12" height="{}" fill="{}"/>"#,
+            bar_x,
+            MARGIN + bar_height * (1.0 - (i + 1) as f64 / 50.0),
+            bar_height / 50.0 + 0.5,
+            heatmap_color(i as f64 / 49.0)
+        ));
+    }
+
+    for (value, y) in [(max, MARGIN - 4.0), (min, HEIGHT - MARGIN + 14.0)] {
+        elements.push(format!(
+            r#"<text x="{}" y="{}" font-size="11">{}</text>"#,
+            bar_x,
+            y,
+            format_tick(value)
+        ));
+    }
+
+    document(elements)
+}
@@ -3,7 +3,9 @@ use std::{
     ops::{Add, Div, Mul, Sub},
 };
 
-#[derive(PartialEq, Debug, Clone)]
+use serde::Serialize;
+
+#[derive(PartialEq, Debug, Clone, Serialize)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -0,0 +1,131 @@
+use std::{
+    fmt::Debug,
+    io::{self, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Gets told how far a run is. Runs check in from a single thread a few times a second, not once
+/// per trial.
+pub trait ProgressObserver: Debug + Send + Sync {
+    /// Called as a run of `total` trials starts
+    fn start(&self, _total: usize) {}
+    /// Called with the trials done so far, returns `false` to cancel the rest of the run
+    fn update(&self, done: usize, total: usize) -> bool;
+    /// Called once the run is over, whether it finished or was cancelled
+    fn finish(&self, _done: usize, _total: usize) {}
+}
+
+/// Reports nothing and never cancels, the runner's default
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressObserver for NoProgress {
+    fn update(&self, _done: usize, _total: usize) -> bool {
+        true
+    }
+}
+
+/// Bar with the percentage done and the estimated time left, redrawn on standard error at most
+/// every `refresh`
+#[derive(Debug)]
+pub struct ProgressBar {
+    /// Characters the bar itself takes up
+    pub width: usize,
+    pub refresh: Duration,
+    /// When the run started and when the bar was last drawn
+    times: Mutex<Option<(Instant, Instant)>>,
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        ProgressBar::new()
+    }
+}
+
+impl ProgressBar {
+    pub fn new() -> Self {
+        ProgressBar {
+            width: 40,
+            refresh: Duration::from_millis(250),
+            times: Mutex::new(None),
+        }
+    }
+
+    fn draw(&self, done: usize, total: usize, elapsed: Duration) {
+        let fraction = done as f64 / total.max(1) as f64;
+        let filled = ((fraction * self.width as f64) as usize).min(self.width);
+
+        let eta = match done {
+            0 => "--:--".to_string(),
+            _ => {
+                let left = elapsed.as_secs_f64() * (total - done.min(total)) as f64 / done as f64;
+                let seconds = left.round() as u64;
+
+                format!("{:02}:{:02}", seconds / 60, seconds % 60)
+            }
+        };
+
+        let mut stderr = io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r[{}{}] {:.2}% {} / {}, ETA {}",
+            "#".repeat(filled),
+            "-".repeat(self.width - filled),
+            fraction * 100.0,
+            done,
+            total,
+            eta
+        );
+        let _ = stderr.flush();
+    }
+}
+
+impl ProgressObserver for ProgressBar {
+    fn start(&self, total: usize) {
+        let now = Instant::now();
+
+        *self.times.lock().expect("Bad progress lock") = Some((now, now));
+        self.draw(0, total, Duration::ZERO);
+    }
+
+    fn update(&self, done: usize, total: usize) -> bool {
+        let mut times = self.times.lock().expect("Bad progress lock");
+        let now = Instant::now();
+        let (started, drawn) = times.get_or_insert((now, now));
+
+        if now.duration_since(*drawn) >= self.refresh {
+            *drawn = now;
+            self.draw(done, total, now.duration_since(*started));
+        }
+
+        true
+    }
+
+    fn finish(&self, done: usize, total: usize) {
+        let elapsed = self
+            .times
+            .lock()
+            .expect("Bad progress lock")
+            .take()
+            .map_or(Duration::ZERO, |(started, _)| started.elapsed());
+
+        self.draw(done, total, elapsed);
+        eprintln!();
+    }
+}
+
+/// Hands the progress to a function, which returns `false` to cancel the run
+pub struct ProgressCallback<F: Fn(usize, usize) -> bool + Send + Sync>(pub F);
+
+impl<F: Fn(usize, usize) -> bool + Send + Sync> Debug for ProgressCallback<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ProgressCallback")
+    }
+}
+
+impl<F: Fn(usize, usize) -> bool + Send + Sync> ProgressObserver for ProgressCallback<F> {
+    fn update(&self, done: usize, total: usize) -> bool {
+        (self.0)(done, total)
+    }
+}
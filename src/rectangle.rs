@@ -1,12 +1,14 @@
 use std::{fmt::Display, ops::RangeInclusive};
 
+use serde::Serialize;
+
 use crate::{
     circle::Circle,
     point::Point,
     two_dim_shape::{Contains, TwoDimShape},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Rectangle {
     pub pt: Point,
     pub width: f64,
@@ -61,6 +63,17 @@ impl TwoDimShape for Rectangle {
     fn calc_max_span(&self) -> f64 {
         (self.width.powi(2) + self.height.powi(2)).sqrt()
     }
+
+    fn calc_bounding_box(&self) -> Rectangle {
+        self.clone()
+    }
+
+    fn to_svg(&self) -> String {
+        format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}"/>"#,
+            self.pt.x, self.pt.y, self.width, self.height
+        )
+    }
 }
 
 impl Contains for Rectangle {
@@ -0,0 +1,180 @@
+use std::{
+    fmt::Display,
+    io::{self, BufRead, Write},
+};
+
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    localizer::Localizer,
+    sink::TrialSink,
+    test_runner::{TestResult, TestRunner},
+    trial_csv::{TrialCsvError, TrialReader, TrialWriter},
+};
+
+/// Whether a trial missed the tag by more than `threshold` or got no fix at all
+pub fn is_failure(result: &TestResult, threshold: f64) -> bool {
+    result.delta.is_nan() || result.delta > threshold
+}
+
+/// Keeps the failed trials of a run in a replay file, a trial CSV that [`replay`] and
+/// [`TrialReader`] read back
+#[derive(Debug)]
+pub struct FailureCapture<W: Write> {
+    /// See [`is_failure`]
+    pub threshold: f64,
+    /// Trials written so far
+    pub captured: usize,
+    writer: TrialWriter<W>,
+}
+
+impl<W: Write> FailureCapture<W> {
+    /// Starts the replay file for trials with `anchors` anchors each
+    pub fn new(writer: W, anchors: usize, threshold: f64) -> io::Result<Self> {
+        Ok(FailureCapture {
+            threshold,
+            captured: 0,
+            writer: TrialWriter::new(writer, anchors)?,
+        })
+    }
+
+    /// Writes `result` out when it is a failure, returning whether it was
+    pub fn capture(&mut self, result: &TestResult) -> io::Result<bool> {
+        if !is_failure(result, self.threshold) {
+            return Ok(false);
+        }
+
+        self.writer.write(result)?;
+        self.captured += 1;
+
+        Ok(true)
+    }
+
+    /// Flushes the trials still buffered and hands back the underlying writer
+    pub fn finish(self) -> io::Result<W> {
+        self.writer.finish()
+    }
+}
+
+impl<W: Write> TrialSink for FailureCapture<W> {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        self.capture(result).map(|_| ())
+    }
+}
+
+/// A captured trial solved again
+#[derive(Debug)]
+pub struct Replayed {
+    /// The trial as it was captured
+    pub original: TestResult,
+    /// The same measurements with the fix, delta, failure and uncertainty of the replaying
+    /// localizer and runner
+    pub result: TestResult,
+}
+
+/// The localizer of a replay cannot solve the measurements of the file
+#[derive(Debug)]
+pub struct MeasurementMismatch {
+    /// See [`Localizer::measurements`]
+    pub needed: &'static str,
+    /// See [`TestResult::measurements`]
+    pub measured: &'static str,
+}
+
+impl Display for MeasurementMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Localizer needs {} but the trials measured {}",
+            self.needed, self.measured
+        )
+    }
+}
+
+/// Why a trial of a replay file could not be replayed
+#[derive(Debug)]
+pub enum ReplayError {
+    Csv(TrialCsvError),
+    Mismatch(MeasurementMismatch),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Csv(err) => write!(f, "{}", err),
+            ReplayError::Mismatch(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<TrialCsvError> for ReplayError {
+    fn from(err: TrialCsvError) -> Self {
+        ReplayError::Csv(err)
+    }
+}
+
+impl From<MeasurementMismatch> for ReplayError {
+    fn from(err: MeasurementMismatch) -> Self {
+        ReplayError::Mismatch(err)
+    }
+}
+
+/// Feeds the measurements of every trial of a replay file to `localizer`, which has to accept the
+/// kind of measurements each trial was captured with, see [`Localizer::accepts`]. The uncertainty
+/// of every fix comes from the noise and mode of `runner`, which should be set up like the run
+/// the trials came from.
+pub fn replay<'a, R: BufRead + 'a, G: Rng + SeedableRng>(
+    reader: TrialReader<R>,
+    localizer: Localizer,
+    runner: &'a TestRunner<G>,
+) -> impl Iterator<Item = Result<Replayed, ReplayError>> + 'a {
+    reader.map(move |original| {
+        let original = original?;
+
+        if !localizer.accepts(&original) {
+            return Err(MeasurementMismatch {
+                needed: localizer.measurements(),
+                measured: original.measurements(),
+            }
+            .into());
+        }
+
+        let mut result = original.clone();
+        result.set_fix(localizer.solve_measurements(&original));
+        result.uncertainty = runner.result_uncertainty(&result);
+
+        Ok(Replayed { original, result })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{multilateration::paper_way, rectangle::Rectangle};
+
+    use super::*;
+
+    #[test]
+    fn every_trial_is_checked_against_the_localizer() {
+        let mut runner = TestRunner::new(4, 0.0, Rectangle::new(0.0, 0.0, 100.0, 100.0), paper_way);
+        Localizer::from_name("chan_taylor")
+            .expect("Bad localizer name")
+            .configure(&mut runner, 0.0, 0.0, None);
+
+        let mut writer = TrialWriter::new(Vec::new(), 4).expect("Bad write");
+        writer.write(&runner.run_iteration(0)).expect("Bad write");
+        writer.write(&runner.run_iteration(1)).expect("Bad write");
+
+        // Breaks the seed of the first trial, so only the second one can be read
+        let csv = String::from_utf8(writer.finish().expect("Bad write")).expect("Bad UTF-8");
+        let (header, rows) = csv.split_once('\n').expect("Bad header");
+        let csv = format!("{}\nx{}", header, rows);
+
+        let reader = TrialReader::new(csv.as_bytes()).expect("Bad header");
+        let localizer = Localizer::from_name("paper_way").expect("Bad localizer name");
+        let replayed = replay(reader, localizer, &runner).collect::<Vec<_>>();
+
+        assert_eq!(replayed.len(), 2);
+        assert!(matches!(replayed[0], Err(ReplayError::Csv(_))));
+        assert!(matches!(replayed[1], Err(ReplayError::Mismatch(_))));
+    }
+}
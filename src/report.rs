@@ -0,0 +1,384 @@
+use std::{fmt::Display, str::FromStr};
+
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{rectangle::Rectangle, stats, test_runner::TestRunner, two_dim_shape::TwoDimShape};
+
+/// Fractions of the bounds' largest span a fix may be off by before it is non compliant
+pub const COMPLIANCE_THRESHOLDS: [f64; 3] = [0.05, 0.10, 0.20];
+
+/// Standard deviations from the mean past which a delta counts as an outlier
+pub const OUTLIER_THRESHOLD: f64 = 2.0;
+
+/// Value to 2 decimals, `-` when there is none
+pub fn format_value(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |v| format!("{:.2}", v))
+}
+
+/// Value at full precision for CSV cells, empty when there is none
+pub fn format_csv_value(value: Option<f64>) -> String {
+    value.map_or(String::new(), |v| v.to_string())
+}
+
+pub fn format_percent(fraction: f64) -> String {
+    format!("{:.2}%", fraction * 100.0)
+}
+
+/// Markdown table with a header line
+pub fn markdown_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let line = |cells: &[String]| format!("| {} |", cells.join(" | "));
+
+    [
+        line(&header.iter().map(|h| h.to_string()).collect::<Vec<_>>()),
+        line(&vec!["---".to_string(); header.len()]),
+    ]
+    .into_iter()
+    .chain(rows.iter().map(|row| line(row)))
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+/// How a report is written out
+#[derive(PartialEq, Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Json,
+    Markdown,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "markdown" => Ok(ReportFormat::Markdown),
+            _ => Err("expected one of text, json, markdown".to_string()),
+        }
+    }
+}
+
+/// What a run simulated
+#[derive(Debug, Clone, Serialize)]
+pub struct ExperimentConfig {
+    /// Name of the solver the fixes came from
+    pub localizer: String,
+    pub mode: String,
+    /// Anchors every trial had, see [`TestRunner::anchor_count`]
+    pub num_of_anchors: usize,
+    pub error_margin: f64,
+    /// The runner's noise model, `None` for its default `error_margin` noise
+    pub noise: Option<String>,
+    pub bounds: Rectangle,
+    pub trials: usize,
+    pub seed: u64,
+}
+
+impl ExperimentConfig {
+    /// Configuration of `trials` iterations of `runner`, whose callback is called `localizer`
+    pub fn new<R: Rng + SeedableRng>(
+        localizer: &str,
+        runner: &TestRunner<R>,
+        trials: usize,
+    ) -> Self {
+        ExperimentConfig {
+            localizer: localizer.to_string(),
+            mode: runner.mode.name().to_string(),
+            num_of_anchors: runner.anchor_count(),
+            error_margin: runner.error_margin,
+            noise: runner.noise.as_ref().map(|noise| noise.to_string()),
+            bounds: runner.bounds().clone(),
+            trials,
+            seed: runner.seed,
+        }
+    }
+
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Localizer", self.localizer.clone()),
+            ("Mode", self.mode.clone()),
+            ("Anchors", self.num_of_anchors.to_string()),
+            ("Error Margin", self.error_margin.to_string()),
+            ("Noise", self.noise.clone().unwrap_or("Default".to_string())),
+            ("Bounds", self.bounds.to_string()),
+            ("Trials", self.trials.to_string()),
+            ("Seed", self.seed.to_string()),
+        ]
+    }
+
+    /// Setting and value table
+    pub fn to_markdown(&self) -> String {
+        let rows = self
+            .rows()
+            .into_iter()
+            .map(|(key, value)| vec![key.to_string(), value])
+            .collect::<Vec<_>>();
+
+        markdown_table(&["Setting", "Value"], &rows)
+    }
+}
+
+impl Display for ExperimentConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows_str = self
+            .rows()
+            .into_iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        write!(f, "{}", rows_str)
+    }
+}
+
+/// Summary statistics of the deltas of a run, `None` where there were too few fixes
+#[derive(PartialEq, Debug, Clone, Serialize)]
+pub struct Summary {
+    /// Trials with a fix
+    pub count: usize,
+    /// Trials whose delta is NaN, left out of every statistic
+    pub failed: usize,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub std: Option<f64>,
+    pub rmse: Option<f64>,
+    pub cep50: Option<f64>,
+    pub cep95: Option<f64>,
+    pub max: Option<f64>,
+    /// Deltas more than [`OUTLIER_THRESHOLD`] standard deviations from the mean
+    pub outliers: usize,
+}
+
+impl Summary {
+    pub fn new(deltas: &[f64]) -> Self {
+        let acc = deltas.iter().copied().collect::<stats::Accumulator>();
+        let [median, cep95] =
+            stats::quantiles(deltas, &[0.5, 0.95]).map_or([None; 2], |q| [Some(q[0]), Some(q[1])]);
+
+        Summary {
+            count: acc.count,
+            failed: deltas.len() - acc.count,
+            mean: acc.mean(),
+            median,
+            std: acc.std(),
+            rmse: acc.rms(),
+            cep50: median,
+            cep95,
+            max: acc.max(),
+            outliers: stats::identify_outliers(deltas, OUTLIER_THRESHOLD).len(),
+        }
+    }
+
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Fixes", self.count.to_string()),
+            ("Failed", self.failed.to_string()),
+            ("Mean", format_value(self.mean)),
+            ("Median", format_value(self.median)),
+            ("Standard Deviation", format_value(self.std)),
+            ("RMSE", format_value(self.rmse)),
+            ("CEP50", format_value(self.cep50)),
+            ("CEP95", format_value(self.cep95)),
+            ("Max", format_value(self.max)),
+            ("Outliers", self.outliers.to_string()),
+        ]
+    }
+}
+
+/// How many trials missed the tag by more than a fraction of the bounds' largest span or got no
+/// fix at all
+#[derive(PartialEq, Debug, Clone, Serialize)]
+pub struct Compliance {
+    pub threshold: f64,
+    /// `threshold` as a distance
+    pub max_delta: f64,
+    pub non_compliant: usize,
+    /// `non_compliant` out of every trial, failed ones included
+    pub fraction: f64,
+}
+
+impl Compliance {
+    pub fn new(deltas: &[f64], max_span: f64, threshold: f64) -> Self {
+        let max_delta = max_span * threshold;
+        let non_compliant = deltas
+            .iter()
+            .filter(|v| v.is_nan() || **v > max_delta)
+            .count();
+
+        Compliance {
+            threshold,
+            max_delta,
+            non_compliant,
+            fraction: non_compliant as f64 / deltas.len().max(1) as f64,
+        }
+    }
+
+    fn row(&self, trials: usize) -> Vec<String> {
+        vec![
+            format_percent(self.threshold),
+            format!("{:.2}", self.max_delta),
+            format!("{} / {}", self.non_compliant, trials),
+            format_percent(self.fraction),
+        ]
+    }
+}
+
+/// Configuration and results of a run, for comparing and archiving runs
+#[derive(Debug, Clone, Serialize)]
+pub struct ExperimentReport {
+    pub config: ExperimentConfig,
+    pub summary: Summary,
+    /// One entry per [`COMPLIANCE_THRESHOLDS`] fraction
+    pub compliance: Vec<Compliance>,
+    /// Trials off by more than the bounds' whole largest span
+    pub completely_wrong: Compliance,
+}
+
+impl ExperimentReport {
+    /// Report of the `deltas` of every trial of a run
+    pub fn new(config: ExperimentConfig, deltas: &[f64]) -> Self {
+        let max_span = config.bounds.calc_max_span();
+
+        ExperimentReport {
+            summary: Summary::new(deltas),
+            compliance: COMPLIANCE_THRESHOLDS
+                .iter()
+                .map(|threshold| Compliance::new(deltas, max_span, *threshold))
+                .collect(),
+            completely_wrong: Compliance::new(deltas, max_span, 1.0),
+            config,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_string(),
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Bad report serialization")
+    }
+
+    /// Configuration, summary and compliance as three Markdown tables
+    pub fn to_markdown(&self) -> String {
+        let key_values = |rows: Vec<(&str, String)>| {
+            rows.into_iter()
+                .map(|(key, value)| vec![key.to_string(), value])
+                .collect::<Vec<_>>()
+        };
+        let trials = self.config.trials;
+
+        let compliance = self
+            .compliance
+            .iter()
+            .map(|compliance| compliance.row(trials))
+            .chain(std::iter::once({
+                let mut row = self.completely_wrong.row(trials);
+                row[0] = format!("{} (Completely Wrong)", row[0]);
+                row
+            }))
+            .collect::<Vec<_>>();
+
+        [
+            "## Configuration".to_string(),
+            self.config.to_markdown(),
+            "## Summary".to_string(),
+            markdown_table(&["Statistic", "Value"], &key_values(self.summary.rows())),
+            "## Compliance".to_string(),
+            markdown_table(
+                &["Threshold", "Max Delta", "Non Compliant", "Fraction"],
+                &compliance,
+            ),
+        ]
+        .join("\n\n")
+    }
+}
+
+impl Display for ExperimentReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let trials = self.config.trials;
+        writeln!(f, "{}", self.config)?;
+        writeln!(f)?;
+
+        for (key, value) in self.summary.rows() {
+            writeln!(f, "{}: {}", key, value)?;
+        }
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "Completely Wrong: {} / {} ({})",
+            self.completely_wrong.non_compliant,
+            trials,
+            format_percent(self.completely_wrong.fraction)
+        )?;
+
+        for compliance in &self.compliance {
+            writeln!(
+                f,
+                "Non Compliant ({:.0}%): {} / {} ({})",
+                compliance.threshold * 100.0,
+                compliance.non_compliant,
+                trials,
+                format_percent(compliance.fraction)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{multilateration::paper_way, placement::AnchorPlacement, point::Point};
+
+    use super::*;
+
+    fn report(deltas: &[f64]) -> ExperimentReport {
+        let mut runner = TestRunner::new(3, 0.05, Rectangle::new(0.0, 0.0, 60.0, 80.0), paper_way);
+        runner.placement = AnchorPlacement::Fixed(vec![Point::new(0.0, 0.0); 5]);
+        runner.seed = 1;
+
+        ExperimentReport::new(
+            ExperimentConfig::new("paper_way", &runner, deltas.len()),
+            deltas,
+        )
+    }
+
+    #[test]
+    fn failed_trials_are_counted_and_non_compliant() {
+        let report = report(&[1.0, 2.0, 3.0, f64::NAN, 200.0]);
+
+        assert_eq!(report.summary.count, 4);
+        assert_eq!(report.summary.failed, 1);
+        assert_eq!(report.summary.max, Some(200.0));
+
+        // The bounds' largest span is 100
+        assert_eq!(report.compliance[0].max_delta, 5.0);
+        assert_eq!(report.compliance[0].non_compliant, 2);
+        assert_eq!(report.compliance[0].fraction, 0.4);
+        assert_eq!(report.completely_wrong.non_compliant, 2);
+    }
+
+    #[test]
+    fn renderings_carry_the_configuration() {
+        let report = report(&[1.0, f64::NAN]);
+
+        assert_eq!(report.config.num_of_anchors, 5);
+        assert!(report.to_string().contains("Anchors: 5"));
+        assert!(report.to_markdown().contains("| Anchors | 5 |"));
+
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).expect("Bad JSON");
+
+        assert_eq!(json["config"]["num_of_anchors"], 5);
+        assert_eq!(json["summary"]["failed"], 1);
+        assert_eq!(json["summary"]["std"], serde_json::Value::Null);
+    }
+}
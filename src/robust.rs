@@ -0,0 +1,445 @@
+use rand::{rngs::StdRng, seq::index, Rng, SeedableRng};
+
+use crate::{matrix::Matrix, multilateration::linear_least_squares, point::Point};
+
+/// Position estimate together with the anchors that were rejected as outliers
+#[derive(PartialEq, Debug, Clone)]
+pub struct RobustEstimate {
+    pub pt: Point,
+    /// Indices into the anchor list
+    pub outliers: Vec<usize>,
+}
+
+/// Range residual relative to the measured distance, matching the multiplicative error model
+fn relative_residual(anchor: &Point, distance: f64, pt: &Point) -> f64 {
+    (distance - anchor.distance_to(pt)) / distance.max(f64::EPSILON)
+}
+
+/// Anchors per minimal subset, the least the linear solver can work with
+const SAMPLE_SIZE: usize = 3;
+
+/// Every subset of `SAMPLE_SIZE` anchors when there are at most `max_subsets` of them, otherwise
+/// `max_subsets` random ones
+fn subsets(n: usize, max_subsets: usize, rng: &mut impl Rng) -> Vec<Vec<usize>> {
+    let k = SAMPLE_SIZE;
+    let mut combinations = vec![];
+    let mut combination = (0..k).collect::<Vec<_>>();
+
+    // Enumerate subsets in lexicographic order until they run out or get too many
+    loop {
+        combinations.push(combination.clone());
+
+        if combinations.len() > max_subsets {
+            break;
+        }
+
+        match (0..k).rev().find(|i| combination[*i] != i + n - k) {
+            Some(i) => {
+                combination[i] += 1;
+
+                for j in (i + 1)..k {
+                    combination[j] = combination[j - 1] + 1;
+                }
+            }
+            None => return combinations,
+        }
+    }
+
+    (0..max_subsets)
+        .map(|_| index::sample(rng, n, k).into_vec())
+        .collect()
+}
+
+/// Linear fix from only the anchors in `subset`
+fn subset_fix(anchors: &[Point], distances: &[f64], subset: &[usize]) -> Option<Point> {
+    let subset_anchors = subset
+        .iter()
+        .map(|i| anchors[*i].clone())
+        .collect::<Vec<_>>();
+    let subset_distances = subset.iter().map(|i| distances[*i]).collect::<Vec<_>>();
+
+    linear_least_squares(&subset_anchors, &subset_distances)
+}
+
+/// Random sample consensus over minimal anchor subsets
+#[derive(PartialEq, Debug, Clone)]
+pub struct Ransac {
+    /// Upper bound on the number of subsets tried, when there are fewer subsets than this every
+    /// one of them is tried instead of sampling
+    pub max_iterations: usize,
+    /// An anchor is an inlier when its range residual is within this fraction of its distance
+    pub threshold: f64,
+}
+
+impl Ransac {
+    pub fn new(max_iterations: usize, threshold: f64) -> Self {
+        Ransac {
+            max_iterations,
+            threshold,
+        }
+    }
+
+    /// Returns the consensus estimate, refined over all of its inliers when there are enough
+    pub fn solve(
+        &self,
+        anchors: &[Point],
+        distances: &[f64],
+        rng: &mut impl Rng,
+    ) -> Option<RobustEstimate> {
+        let n = anchors.len();
+
+        if n < SAMPLE_SIZE || distances.len() != n {
+            return None;
+        }
+
+        let mut best: Option<(Point, Vec<usize>, f64)> = None;
+
+        for subset in subsets(n, self.max_iterations, rng) {
+            let pt = match subset_fix(anchors, distances, &subset) {
+                Some(pt) => pt,
+                None => continue,
+            };
+
+            let residuals = (0..n)
+                .map(|i| relative_residual(&anchors[i], distances[i], &pt).abs())
+                .collect::<Vec<_>>();
+            let inliers = (0..n)
+                .filter(|i| residuals[*i] <= self.threshold)
+                .collect::<Vec<_>>();
+            let cost = inliers.iter().map(|i| residuals[*i].powi(2)).sum::<f64>();
+
+            let is_better = match &best {
+                None => true,
+                Some((_, best_inliers, best_cost)) => {
+                    inliers.len() > best_inliers.len()
+                        || (inliers.len() == best_inliers.len() && cost < *best_cost)
+                }
+            };
+
+            if is_better {
+                best = Some((pt, inliers, cost));
+            }
+        }
+
+        let (pt, inliers, _) = best?;
+
+        Some(RobustEstimate {
+            pt: subset_fix(anchors, distances, &inliers).unwrap_or(pt),
+            outliers: (0..n).filter(|i| !inliers.contains(i)).collect(),
+        })
+    }
+}
+
+impl Default for Ransac {
+    fn default() -> Self {
+        Ransac::new(100, 0.1)
+    }
+}
+
+/// Loss functions for M-estimation, each holding its tuning constant in units of the residual scale
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RobustLoss {
+    /// Quadratic up to `k`, linear beyond it. Anchors past `k` are only down weighted.
+    Huber(f64),
+    /// Tukey's biweight, anchors past `c` get no weight at all
+    Tukey(f64),
+}
+
+impl RobustLoss {
+    /// The usual tuning constants, giving 95% efficiency for normally distributed noise
+    pub const HUBER: RobustLoss = RobustLoss::Huber(1.345);
+    pub const TUKEY: RobustLoss = RobustLoss::Tukey(4.685);
+
+    /// IRLS weight for a residual divided by the scale
+    pub fn weight(&self, standardized: f64) -> f64 {
+        let u = standardized.abs();
+
+        match *self {
+            RobustLoss::Huber(k) => {
+                if u <= k {
+                    1.0
+                } else {
+                    k / u
+                }
+            }
+            RobustLoss::Tukey(c) => {
+                if u <= c {
+                    (1.0 - (u / c).powi(2)).powi(2)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Whether a residual divided by the scale is past the tuning constant
+    pub fn is_outlier(&self, standardized: f64) -> bool {
+        match *self {
+            RobustLoss::Huber(limit) | RobustLoss::Tukey(limit) => standardized.abs() > limit,
+        }
+    }
+}
+
+/// Iteratively reweighted least squares on the range equations
+#[derive(PartialEq, Debug, Clone)]
+pub struct Irls {
+    pub loss: RobustLoss,
+    /// Lower bound of the relative residual scale, which is otherwise estimated every iteration
+    /// from the median absolute deviation of the residuals
+    pub min_scale: f64,
+    pub max_iterations: usize,
+}
+
+impl Irls {
+    pub fn new(loss: RobustLoss) -> Self {
+        Irls {
+            loss,
+            min_scale: 0.01,
+            max_iterations: 20,
+        }
+    }
+
+    fn scale(&self, residuals: &[f64]) -> f64 {
+        let mut deviations = residuals.iter().map(|r| r.abs()).collect::<Vec<_>>();
+        deviations.sort_by(|a, b| a.total_cmp(b));
+
+        let mid = deviations.len() / 2;
+        let median = if deviations.len().is_multiple_of(2) {
+            (deviations[mid - 1] + deviations[mid]) / 2.0
+        } else {
+            deviations[mid]
+        };
+
+        // 1.4826 makes the MAD a consistent estimator of a normal standard deviation
+        (1.4826 * median).max(self.min_scale)
+    }
+
+    /// Starts from the coordinate-wise median of the fixes of up to 100 minimal anchor subsets,
+    /// since a plain least squares start is already dragged off by the outliers. See
+    /// [`Irls::solve_from`].
+    pub fn solve(
+        &self,
+        anchors: &[Point],
+        distances: &[f64],
+        rng: &mut impl Rng,
+    ) -> Option<RobustEstimate> {
+        if anchors.len() < SAMPLE_SIZE || distances.len() != anchors.len() {
+            return None;
+        }
+
+        let fixes = subsets(anchors.len(), 100, rng)
+            .iter()
+            .filter_map(|subset| subset_fix(anchors, distances, subset))
+            .collect::<Vec<_>>();
+
+        if fixes.is_empty() {
+            return None;
+        }
+
+        let median = |mut values: Vec<f64>| {
+            values.sort_by(|a, b| a.total_cmp(b));
+
+            values[values.len() / 2]
+        };
+        let initial = Point::new(
+            median(fixes.iter().map(|pt| pt.x).collect()),
+            median(fixes.iter().map(|pt| pt.y).collect()),
+        );
+
+        self.solve_from(anchors, distances, &initial)
+    }
+
+    /// Reweights the residuals at every Gauss-Newton step, starting from `initial`
+    pub fn solve_from(
+        &self,
+        anchors: &[Point],
+        distances: &[f64],
+        initial: &Point,
+    ) -> Option<RobustEstimate> {
+        let n = anchors.len();
+
+        if n < 2 || distances.len() != n {
+            return None;
+        }
+
+        let mut pt = initial.clone();
+
+        for _ in 0..self.max_iterations {
+            let residuals = (0..n)
+                .map(|i| relative_residual(&anchors[i], distances[i], &pt))
+                .collect::<Vec<_>>();
+            let scale = self.scale(&residuals);
+            let weights = residuals
+                .iter()
+                .map(|r| self.loss.weight(r / scale))
+                .collect::<Vec<_>>();
+            let cost = |pt: &Point| {
+                (0..n)
+                    .map(|i| weights[i] * relative_residual(&anchors[i], distances[i], pt).powi(2))
+                    .sum::<f64>()
+            };
+
+            // Rows are scaled by sqrt(weight) / distance so the normal equations minimise the
+            // weighted relative residuals
+            let mut jacobian = Matrix::new(n, 2);
+            let mut errors = Matrix::new(n, 1);
+
+            for i in 0..n {
+                let delta = &pt - &anchors[i];
+                let dist = delta.pow(2).sum().sqrt();
+                let factor = weights[i].sqrt() / distances[i].max(f64::EPSILON);
+
+                jacobian.set(i, 0, factor * delta.x / dist)?;
+                jacobian.set(i, 1, factor * delta.y / dist)?;
+                errors.set(i, 0, factor * (distances[i] - dist))?;
+            }
+
+            // Every anchor can end up with no weight at all under Tukey's loss
+            let jacobian_t = jacobian.transpose();
+            let step = match jacobian_t
+                .dot(&jacobian)
+                .and_then(|m| m.invert())
+                .and_then(|m| m.dot(&jacobian_t))
+                .and_then(|m| m.dot(&errors))
+            {
+                Some(step) => step,
+                None => break,
+            };
+            let mut step = Point::new(*step.get(0, 0)?, *step.get(1, 0)?);
+
+            // Halve steps that overshoot, the range equations are far from linear
+            let current_cost = cost(&pt);
+            let mut next = &pt + &step;
+
+            for _ in 0..10 {
+                if cost(&next) <= current_cost {
+                    break;
+                }
+
+                step = &step / 2.0;
+                next = &pt + &step;
+            }
+
+            pt = next;
+
+            if !(pt.x.is_finite() && pt.y.is_finite()) {
+                return None;
+            }
+
+            if step.pow(2).sum().sqrt() < 1e-9 {
+                break;
+            }
+        }
+
+        let residuals = (0..n)
+            .map(|i| relative_residual(&anchors[i], distances[i], &pt))
+            .collect::<Vec<_>>();
+        let scale = self.scale(&residuals);
+
+        Some(RobustEstimate {
+            outliers: (0..n)
+                .filter(|i| self.loss.is_outlier(residuals[*i] / scale))
+                .collect(),
+            pt,
+        })
+    }
+}
+
+/// RANSAC with the default settings, sampling deterministically so a trial can be replayed
+pub fn ransac_way(anchors: &[Point], distances: &[f64]) -> Option<Point> {
+    Ransac::default()
+        .solve(anchors, distances, &mut StdRng::seed_from_u64(0))
+        .map(|estimate| estimate.pt)
+}
+
+pub fn huber_way(anchors: &[Point], distances: &[f64]) -> Option<Point> {
+    Irls::new(RobustLoss::HUBER)
+        .solve(anchors, distances, &mut StdRng::seed_from_u64(0))
+        .map(|estimate| estimate.pt)
+}
+
+pub fn tukey_way(anchors: &[Point], distances: &[f64]) -> Option<Point> {
+    Irls::new(RobustLoss::TUKEY)
+        .solve(anchors, distances, &mut StdRng::seed_from_u64(0))
+        .map(|estimate| estimate.pt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIASED: usize = 2;
+
+    fn tag() -> Point {
+        Point::new(40.0, 60.0)
+    }
+
+    /// Exact ranges from six anchors around the tag, except for one with a large positive bias
+    fn measurements() -> (Vec<Point>, Vec<f64>) {
+        let anchors = vec![
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(100.0, 100.0),
+            Point::new(0.0, 100.0),
+            Point::new(50.0, -20.0),
+            Point::new(-20.0, 50.0),
+        ];
+        let distances = anchors
+            .iter()
+            .enumerate()
+            .map(|(i, anchor)| anchor.distance_to(&tag()) + if i == BIASED { 30.0 } else { 0.0 })
+            .collect();
+
+        (anchors, distances)
+    }
+
+    fn assert_rejects_bias(estimate: Option<RobustEstimate>, tolerance: f64) {
+        let estimate = estimate.expect("Bad missing estimate");
+
+        assert_eq!(estimate.outliers, vec![BIASED]);
+        assert!(
+            estimate.pt.distance_to(&tag()) < tolerance,
+            "{} is too far from {}",
+            estimate.pt,
+            tag()
+        );
+    }
+
+    #[test]
+    fn ransac_rejects_the_biased_anchor() {
+        let (anchors, distances) = measurements();
+
+        assert_rejects_bias(
+            Ransac::default().solve(&anchors, &distances, &mut StdRng::seed_from_u64(0)),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn irls_rejects_the_biased_anchor() {
+        let (anchors, distances) = measurements();
+
+        // Huber's loss still gives the biased anchor some weight, so its fix is only pulled less
+        for (loss, tolerance) in [(RobustLoss::HUBER, 0.5), (RobustLoss::TUKEY, 1e-3)] {
+            assert_rejects_bias(
+                Irls::new(loss).solve(&anchors, &distances, &mut StdRng::seed_from_u64(0)),
+                tolerance,
+            );
+        }
+    }
+
+    #[test]
+    fn too_few_anchors_have_no_estimate() {
+        let (anchors, distances) = measurements();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(
+            Ransac::default().solve(&anchors[..2], &distances[..2], &mut rng),
+            None
+        );
+        assert_eq!(
+            Irls::new(RobustLoss::HUBER).solve(&anchors[..2], &distances[..2], &mut rng),
+            None
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt::Display;
+
+/// Log-distance path loss model, `rssi = reference_power - 10 * exponent * log10(d / d_0)` with
+/// normally distributed shadowing on top
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PathLossModel {
+    /// Path loss exponent, 2 in free space and typically 2 - 4 indoors
+    pub exponent: f64,
+    /// Received power in dBm at `reference_distance`
+    pub reference_power: f64,
+    pub reference_distance: f64,
+    /// Standard deviation of the shadowing in dB
+    pub shadowing_std: f64,
+}
+
+impl PathLossModel {
+    pub fn new(exponent: f64, reference_power: f64, shadowing_std: f64) -> Self {
+        PathLossModel {
+            exponent,
+            reference_power,
+            reference_distance: 1.0,
+            shadowing_std,
+        }
+    }
+
+    /// Mean RSSI in dBm at `distance`
+    pub fn rssi(&self, distance: f64) -> f64 {
+        self.reference_power - 10.0 * self.exponent * (distance / self.reference_distance).log10()
+    }
+
+    /// Inverts the model, turning an RSSI reading back into a range estimate
+    pub fn range(&self, rssi: f64) -> f64 {
+        self.reference_distance * 10f64.powf((self.reference_power - rssi) / (10.0 * self.exponent))
+    }
+}
+
+impl Default for PathLossModel {
+    /// Typical BLE beacon, -59 dBm at 1m in free space with 4dB of shadowing
+    fn default() -> Self {
+        PathLossModel::new(2.0, -59.0, 4.0)
+    }
+}
+
+/// RSSI readings in dBm for each anchor
+#[derive(PartialEq, Debug, Clone)]
+pub struct RssiMeasurement {
+    pub rssi: Vec<f64>,
+}
+
+impl RssiMeasurement {
+    pub fn new(rssi: Vec<f64>) -> Self {
+        RssiMeasurement { rssi }
+    }
+
+    /// Converts every reading into a range estimate with `model`
+    pub fn ranges(&self, model: &PathLossModel) -> Vec<f64> {
+        self.rssi.iter().map(|rssi| model.range(*rssi)).collect()
+    }
+}
+
+impl Display for RssiMeasurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rssi_str = self
+            .rssi
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        write!(f, "{}", rssi_str)
+    }
+}
@@ -0,0 +1,636 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    localizer::Localizer,
+    multilateration::paper_way,
+    noise::{self, NoiseModel},
+    placement::AnchorPlacement,
+    point::Point,
+    rectangle::Rectangle,
+    report::ReportFormat,
+    rssi::PathLossModel,
+    test_runner::TestRunner,
+};
+
+fn default_trials() -> usize {
+    100_000
+}
+
+fn default_error_margin() -> f64 {
+    0.05
+}
+
+fn default_anchor_count() -> usize {
+    3
+}
+
+fn default_angle_std() -> f64 {
+    0.05
+}
+
+fn default_clock_offset_std() -> f64 {
+    1.0
+}
+
+fn default_path_loss_exponent() -> f64 {
+    PathLossModel::default().exponent
+}
+
+fn default_reference_power() -> f64 {
+    PathLossModel::default().reference_power
+}
+
+fn default_shadowing_std() -> f64 {
+    PathLossModel::default().shadowing_std
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    /// The file is not valid TOML or JSON, or `key` does not hold the expected type
+    Parse {
+        key: String,
+        message: String,
+    },
+    /// `key` holds a value the scenario cannot run with
+    Invalid {
+        key: String,
+        message: String,
+    },
+}
+
+impl ScenarioError {
+    fn invalid(key: impl Into<String>, message: impl Into<String>) -> Self {
+        ScenarioError::Invalid {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "{}", err),
+            ScenarioError::Parse { key, message } | ScenarioError::Invalid { key, message } => {
+                write!(f, "{}: {}", key, message)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ScenarioError {
+    fn from(err: io::Error) -> Self {
+        ScenarioError::Io(err)
+    }
+}
+
+impl<E: Display> From<serde_path_to_error::Error<E>> for ScenarioError {
+    fn from(err: serde_path_to_error::Error<E>) -> Self {
+        ScenarioError::Parse {
+            key: err.path().to_string(),
+            message: err.inner().to_string().trim_end().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoundsConfig {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlacementKind {
+    #[default]
+    Random,
+    Corners,
+    Perimeter,
+    Grid,
+    /// The anchors listed in `points`
+    Fixed,
+}
+
+impl PlacementKind {
+    /// The placement of this kind, `points` being the anchors of a fixed one
+    pub fn placement(&self, points: Vec<Point>) -> AnchorPlacement {
+        match self {
+            PlacementKind::Random => AnchorPlacement::Random,
+            PlacementKind::Corners => AnchorPlacement::Corners,
+            PlacementKind::Perimeter => AnchorPlacement::Perimeter,
+            PlacementKind::Grid => AnchorPlacement::Grid,
+            PlacementKind::Fixed => AnchorPlacement::Fixed(points),
+        }
+    }
+}
+
+impl FromStr for PlacementKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(PlacementKind::Random),
+            "corners" => Ok(PlacementKind::Corners),
+            "perimeter" => Ok(PlacementKind::Perimeter),
+            "grid" => Ok(PlacementKind::Grid),
+            "fixed" => Ok(PlacementKind::Fixed),
+            _ => Err("expected one of random, corners, perimeter, grid, fixed".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnchorsConfig {
+    #[serde(default = "default_anchor_count")]
+    pub count: usize,
+    #[serde(default)]
+    pub placement: PlacementKind,
+    /// `[x, y]` of every anchor, only with the fixed placement
+    pub points: Option<Vec<[f64; 2]>>,
+}
+
+impl Default for AnchorsConfig {
+    fn default() -> Self {
+        AnchorsConfig {
+            count: default_anchor_count(),
+            placement: PlacementKind::default(),
+            points: None,
+        }
+    }
+}
+
+/// One model of the noise stack, picked by its `type` key
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum NoiseConfig {
+    Gaussian { std: f64 },
+    Multiplicative { margin: f64 },
+    Bias { offset: f64 },
+    Nlos { probability: f64, mean_bias: f64 },
+    Quantization { step: f64 },
+    Dropout { probability: f64 },
+}
+
+impl NoiseConfig {
+    /// Checks the parameters, naming the offending one relative to `key`
+    fn validate(&self, key: &str) -> Result<(), ScenarioError> {
+        let probability = |name: &str, value: f64| {
+            if (0.0..=1.0).contains(&value) {
+                Ok(())
+            } else {
+                Err(ScenarioError::invalid(
+                    format!("{}.{}", key, name),
+                    "must be between 0 and 1",
+                ))
+            }
+        };
+        let positive = |name: &str, value: f64| {
+            if value > 0.0 && value.is_finite() {
+                Ok(())
+            } else {
+                Err(ScenarioError::invalid(
+                    format!("{}.{}", key, name),
+                    "must be positive",
+                ))
+            }
+        };
+
+        match *self {
+            NoiseConfig::Gaussian { std } => positive("std", std),
+            NoiseConfig::Multiplicative { margin } => probability("margin", margin),
+            NoiseConfig::Bias { offset } if !offset.is_finite() => Err(ScenarioError::invalid(
+                format!("{}.offset", key),
+                "must be finite",
+            )),
+            NoiseConfig::Bias { .. } => Ok(()),
+            NoiseConfig::Nlos {
+                probability: p,
+                mean_bias,
+            } => probability("probability", p).and(positive("mean_bias", mean_bias)),
+            NoiseConfig::Quantization { step } => positive("step", step),
+            NoiseConfig::Dropout { probability: p } => probability("probability", p),
+        }
+    }
+
+    pub fn model(&self) -> Box<dyn NoiseModel> {
+        match *self {
+            NoiseConfig::Gaussian { std } => Box::new(noise::Gaussian::new(std)),
+            NoiseConfig::Multiplicative { margin } => Box::new(noise::Multiplicative::new(margin)),
+            NoiseConfig::Bias { offset } => Box::new(noise::Bias::new(offset)),
+            NoiseConfig::Nlos {
+                probability,
+                mean_bias,
+            } => Box::new(noise::Nlos::new(probability, mean_bias)),
+            NoiseConfig::Quantization { step } => Box::new(noise::Quantization::new(step)),
+            NoiseConfig::Dropout { probability } => Box::new(noise::Dropout::new(probability)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocalizerConfig {
+    /// One of [`crate::localizer::LOCALIZERS`]
+    pub name: String,
+    /// Bearing noise in radians, for the AoA and hybrid localizers
+    #[serde(default = "default_angle_std")]
+    pub angle_std: f64,
+    /// Clock offset noise as a distance, for the TDoA localizers
+    #[serde(default = "default_clock_offset_std")]
+    pub clock_offset_std: f64,
+    /// Ranges from RSSI readings instead of the range noise, for the range localizers
+    pub rssi: Option<RssiConfig>,
+}
+
+/// Log-distance path loss model, see [`PathLossModel`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RssiConfig {
+    #[serde(default = "default_path_loss_exponent")]
+    pub exponent: f64,
+    /// dBm at 1m
+    #[serde(default = "default_reference_power")]
+    pub reference_power: f64,
+    /// dB
+    #[serde(default = "default_shadowing_std")]
+    pub shadowing_std: f64,
+}
+
+impl RssiConfig {
+    /// Checks the parameters, naming the offending one relative to `key`
+    fn validate(&self, key: &str) -> Result<(), ScenarioError> {
+        if !(self.exponent > 0.0 && self.exponent.is_finite()) {
+            return Err(ScenarioError::invalid(
+                format!("{}.exponent", key),
+                "must be positive",
+            ));
+        }
+
+        if !self.reference_power.is_finite() {
+            return Err(ScenarioError::invalid(
+                format!("{}.reference_power", key),
+                "must be finite",
+            ));
+        }
+
+        if !(self.shadowing_std >= 0.0 && self.shadowing_std.is_finite()) {
+            return Err(ScenarioError::invalid(
+                format!("{}.shadowing_std", key),
+                "must not be negative",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn model(&self) -> PathLossModel {
+        PathLossModel::new(self.exponent, self.reference_power, self.shadowing_std)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// Where the report goes, standard output when not set
+    pub report: Option<PathBuf>,
+    /// Where every trial goes as CSV, not written when not set
+    pub trials_csv: Option<PathBuf>,
+}
+
+/// An experiment definition, loaded from a TOML or JSON file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default = "default_trials")]
+    pub trials: usize,
+    /// Random when not set
+    pub seed: Option<u64>,
+    /// Every available core when not set
+    pub threads: Option<usize>,
+    pub bounds: BoundsConfig,
+    #[serde(default)]
+    pub anchors: AnchorsConfig,
+    /// Range noise when `noise` is empty, see [`TestRunner::noise`]
+    #[serde(default = "default_error_margin")]
+    pub error_margin: f64,
+    /// Applied in order, see [`noise::Stack`]
+    #[serde(default)]
+    pub noise: Vec<NoiseConfig>,
+    /// Every localizer runs the same trials
+    pub localizers: Vec<LocalizerConfig>,
+    #[serde(default)]
+    pub output: OutputConfig,
+}
+
+impl Scenario {
+    pub fn from_toml(s: &str) -> Result<Self, ScenarioError> {
+        let scenario: Scenario = serde_path_to_error::deserialize(toml::Deserializer::new(s))?;
+        scenario.validate()?;
+
+        Ok(scenario)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, ScenarioError> {
+        let scenario: Scenario =
+            serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(s))?;
+        scenario.validate()?;
+
+        Ok(scenario)
+    }
+
+    /// Reads a `.json` file as JSON and anything else as TOML
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let contents = fs::read_to_string(path)?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Scenario::from_json(&contents),
+            _ => Scenario::from_toml(&contents),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        if self.trials == 0 {
+            return Err(ScenarioError::invalid("trials", "must be positive"));
+        }
+
+        if i32::try_from(self.trials).is_err() {
+            return Err(ScenarioError::invalid(
+                "trials",
+                format!("must be at most {}", i32::MAX),
+            ));
+        }
+
+        if !(self.bounds.width > 0.0 && self.bounds.width.is_finite()) {
+            return Err(ScenarioError::invalid("bounds.width", "must be positive"));
+        }
+
+        if !(self.bounds.height > 0.0 && self.bounds.height.is_finite()) {
+            return Err(ScenarioError::invalid("bounds.height", "must be positive"));
+        }
+
+        match (self.anchors.placement, &self.anchors.points) {
+            (PlacementKind::Fixed, None) => {
+                return Err(ScenarioError::invalid(
+                    "anchors.points",
+                    "required with the fixed placement",
+                ))
+            }
+            (PlacementKind::Fixed, Some(points)) if points.len() < 2 => {
+                return Err(ScenarioError::invalid(
+                    "anchors.points",
+                    "needs at least 2 anchors",
+                ))
+            }
+            (PlacementKind::Fixed, Some(_)) => {}
+            (_, Some(_)) => {
+                return Err(ScenarioError::invalid(
+                    "anchors.points",
+                    "only used with the fixed placement",
+                ))
+            }
+            (_, None) if self.anchors.count < 2 => {
+                return Err(ScenarioError::invalid(
+                    "anchors.count",
+                    "needs at least 2 anchors",
+                ))
+            }
+            (_, None) if i32::try_from(self.anchors.count).is_err() => {
+                return Err(ScenarioError::invalid(
+                    "anchors.count",
+                    format!("must be at most {}", i32::MAX),
+                ))
+            }
+            (_, None) => {}
+        }
+
+        if !(0.0..1.0).contains(&self.error_margin) {
+            return Err(ScenarioError::invalid(
+                "error_margin",
+                "must be at least 0 and below 1",
+            ));
+        }
+
+        for (i, noise) in self.noise.iter().enumerate() {
+            noise.validate(&format!("noise[{}]", i))?;
+        }
+
+        if self.localizers.is_empty() {
+            return Err(ScenarioError::invalid("localizers", "needs at least one"));
+        }
+
+        for (i, localizer) in self.localizers.iter().enumerate() {
+            let Some(found) = Localizer::from_name(&localizer.name) else {
+                return Err(ScenarioError::invalid(
+                    format!("localizers[{}].name", i),
+                    format!(
+                        "unknown localizer, expected one of {}",
+                        Localizer::names().join(", ")
+                    ),
+                ));
+            };
+
+            if let Some(rssi) = &localizer.rssi {
+                if !found.takes_rssi() {
+                    return Err(ScenarioError::invalid(
+                        format!("localizers[{}].rssi", i),
+                        format!(
+                            "only used with localizers of ranges, not {}",
+                            found.measurements()
+                        ),
+                    ));
+                }
+
+                rssi.validate(&format!("localizers[{}].rssi", i))?;
+            }
+
+            if localizer.angle_std.is_nan() || localizer.angle_std < 0.0 {
+                return Err(ScenarioError::invalid(
+                    format!("localizers[{}].angle_std", i),
+                    "must not be negative",
+                ));
+            }
+
+            if localizer.clock_offset_std.is_nan() || localizer.clock_offset_std < 0.0 {
+                return Err(ScenarioError::invalid(
+                    format!("localizers[{}].clock_offset_std", i),
+                    "must not be negative",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> Rectangle {
+        Rectangle::new(
+            self.bounds.x,
+            self.bounds.y,
+            self.bounds.width,
+            self.bounds.height,
+        )
+    }
+
+    pub fn placement(&self) -> AnchorPlacement {
+        self.anchors.placement.placement(
+            self.anchors
+                .points
+                .iter()
+                .flatten()
+                .map(|[x, y]| Point::new(*x, *y))
+                .collect(),
+        )
+    }
+
+    /// The noise stack, `None` for the runner's default `error_margin` noise
+    pub fn noise_model(&self) -> Option<Box<dyn NoiseModel>> {
+        match self.noise.as_slice() {
+            [] => None,
+            [noise] => Some(noise.model()),
+            models => Some(Box::new(noise::Stack::new(
+                models.iter().map(NoiseConfig::model).collect(),
+            ))),
+        }
+    }
+
+    /// Runner set up for `localizer`, one of the scenario's. Without a seed in the scenario
+    /// every runner gets a different random one.
+    pub fn runner(&self, localizer: &LocalizerConfig) -> TestRunner {
+        let anchor_count = match &self.anchors.points {
+            Some(points) => points.len(),
+            None => self.anchors.count,
+        };
+
+        let mut runner = TestRunner::new(
+            i32::try_from(anchor_count).expect("Bad anchor count"),
+            self.error_margin,
+            self.bounds(),
+            paper_way,
+        );
+
+        runner.placement = self.placement();
+        runner.noise = self.noise_model();
+
+        if let Some(seed) = self.seed {
+            runner.seed = seed;
+        }
+
+        runner.threads = self
+            .threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+        Localizer::from_name(&localizer.name)
+            .expect("Bad localizer name")
+            .configure(
+                &mut runner,
+                localizer.angle_std,
+                localizer.clock_offset_std,
+                localizer.rssi.as_ref().map(RssiConfig::model),
+            );
+
+        runner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = r#"
+        trials = 50
+        seed = 7
+
+        [bounds]
+        x = 0.0
+        y = 0.0
+        width = 60.0
+        height = 80.0
+
+        [anchors]
+        count = 4
+        placement = "corners"
+
+        [[noise]]
+        type = "gaussian"
+        std = 0.5
+
+        [[localizers]]
+        name = "paper_way"
+    "#;
+
+    fn invalid_key(s: &str) -> String {
+        match Scenario::from_toml(s) {
+            Err(ScenarioError::Parse { key, .. }) | Err(ScenarioError::Invalid { key, .. }) => key,
+            other => panic!("Bad result {:?}", other.map(|scenario| scenario.trials)),
+        }
+    }
+
+    #[test]
+    fn scenario_sets_up_the_runner() {
+        let scenario = Scenario::from_toml(SCENARIO).expect("Bad scenario");
+        let runner = scenario.runner(&scenario.localizers[0]);
+
+        assert_eq!(runner.anchor_count(), 4);
+        assert_eq!(runner.placement, AnchorPlacement::Corners);
+        assert_eq!(runner.seed, 7);
+        assert!(runner.noise.is_some());
+    }
+
+    #[test]
+    fn errors_name_the_offending_key() {
+        assert_eq!(
+            invalid_key(&SCENARIO.replace("count = 4", "count = -1")),
+            "anchors.count"
+        );
+        assert_eq!(
+            invalid_key(&SCENARIO.replace("count = 4", "count = 1")),
+            "anchors.count"
+        );
+        assert_eq!(
+            invalid_key(&SCENARIO.replace("count = 4", "count = 4294967296")),
+            "anchors.count"
+        );
+        assert_eq!(
+            invalid_key(&SCENARIO.replace("std = 0.5", "std = -0.5")),
+            "noise[0].std"
+        );
+        assert_eq!(
+            invalid_key(&SCENARIO.replace("paper_way", "nope")),
+            "localizers[0].name"
+        );
+        assert_eq!(
+            invalid_key(&format!("{}\n[localizers.rssi]\nexponent = 0.0", SCENARIO)),
+            "localizers[0].rssi.exponent"
+        );
+        assert_eq!(
+            invalid_key(&format!(
+                "{}\n[localizers.rssi]",
+                SCENARIO.replace("paper_way", "bearing_way")
+            )),
+            "localizers[0].rssi"
+        );
+    }
+
+    #[test]
+    fn rssi_localizers_run_in_rssi_mode() {
+        let scenario =
+            Scenario::from_toml(&format!("{}\n[localizers.rssi]", SCENARIO)).expect("Bad scenario");
+        let runner = scenario.runner(&scenario.localizers[0]);
+
+        assert_eq!(runner.mode.name(), "rssi");
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    circle::Circle, plot::escape, point::Point, rectangle::Rectangle, test_runner::TestResult,
+    two_dim_shape::TwoDimShape,
+};
+
+/// Width of the rendered image, its height follows from the scene's aspect ratio
+const SCENE_WIDTH: f64 = 640.0;
+
+/// Shapes, points and labels to draw to SVG, in world coordinates with `y` pointing up. The view
+/// is fitted around the bounding boxes of everything in the scene.
+pub struct Scene {
+    shapes: Vec<(Box<dyn TwoDimShape>, String)>,
+    points: Vec<(Point, String)>,
+    labels: Vec<(Point, String)>,
+    padding: f64,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene::new()
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            shapes: vec![],
+            points: vec![],
+            labels: vec![],
+            padding: 0.05,
+        }
+    }
+
+    /// Outlines `shape` in `color`, any SVG colour
+    pub fn shape(mut self, shape: impl TwoDimShape + 'static, color: &str) -> Self {
+        self.shapes.push((Box::new(shape), color.to_string()));
+        self
+    }
+
+    pub fn point(mut self, pt: &Point, color: &str) -> Self {
+        self.points.push((pt.clone(), color.to_string()));
+        self
+    }
+
+    /// Writes `text` just above and right of `pt`
+    pub fn label(mut self, pt: &Point, text: &str) -> Self {
+        self.labels.push((pt.clone(), text.to_string()));
+        self
+    }
+
+    /// Room left around the contents as a fraction of their larger side, `0.05` by default
+    pub fn padding(mut self, padding: f64) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Anchors with the range circles of the ones that were not dropped, the true tag and the
+    /// estimate of a single trial
+    pub fn from_result(result: &TestResult) -> Self {
+        let mut scene = Scene::new();
+
+        for (i, anchor) in result.anchor_pts.iter().enumerate() {
+            let dropped = result.dropped.contains(&i);
+
+            if !dropped {
+                scene = scene.shape(
+                    Circle::new(anchor.x, anchor.y, result.adjusted_distances[i].abs()),
+                    "gray",
+                );
+            }
+
+            let label = if dropped {
+                format!("Anchor {} (dropped)", i)
+            } else {
+                format!("Anchor {}", i)
+            };
+
+            scene = scene.point(anchor, "black").label(anchor, &label);
+        }
+
+        scene = scene
+            .point(&result.tag_pt, "green")
+            .label(&result.tag_pt, "Tag");
+
+        if result.predicted_pt.x.is_finite() && result.predicted_pt.y.is_finite() {
+            scene = scene
+                .point(&result.predicted_pt, "red")
+                .label(&result.predicted_pt, "Estimate");
+        }
+
+        scene
+    }
+
+    /// Smallest rectangle around every shape, point and label, `None` for an empty scene
+    pub fn bounding_box(&self) -> Option<Rectangle> {
+        let boxes = self
+            .shapes
+            .iter()
+            .map(|(shape, _)| shape.calc_bounding_box())
+            .chain(
+                self.points
+                    .iter()
+                    .chain(&self.labels)
+                    .map(|(pt, _)| Rectangle::new(pt.x, pt.y, 0.0, 0.0)),
+            )
+            .filter(|bounds| bounds.pt.x.is_finite() && bounds.pt.y.is_finite())
+            .collect::<Vec<_>>();
+
+        if boxes.is_empty() {
+            return None;
+        }
+
+        let min_x = boxes.iter().map(|b| b.pt.x).fold(f64::INFINITY, f64::min);
+        let min_y = boxes.iter().map(|b| b.pt.y).fold(f64::INFINITY, f64::min);
+        let max_x = boxes
+            .iter()
+            .map(|b| b.pt.x + b.width)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let max_y = boxes
+            .iter()
+            .map(|b| b.pt.y + b.height)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        Some(Rectangle::new(min_x, min_y, max_x - min_x, max_y - min_y))
+    }
+
+    pub fn to_svg(&self) -> String {
+        let bounds = self
+            .bounding_box()
+            .unwrap_or(Rectangle::new(0.0, 0.0, 1.0, 1.0));
+
+        // Sizes of the points, lines and text scale with the scene so they look the same at any
+        // zoom
+        let span = bounds.width.max(bounds.height).max(f64::EPSILON);
+        let pad = span * self.padding;
+        let (x, y) = (bounds.pt.x - pad, bounds.pt.y - pad);
+        let (width, height) = (bounds.width + 2.0 * pad, bounds.height + 2.0 * pad);
+        let (radius, stroke, font) = (span / 150.0, span / 400.0, span / 40.0);
+
+        // SVG's y axis points down, so the view is flipped around the x axis
+        let mut elements = vec![format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="white"/>"#,
+            x,
+            -(y + height),
+            width,
+            height
+        )];
+
+        elements.extend(self.shapes.iter().map(|(shape, color)| {
+            format!(
+                r#"<g transform="scale(1 -1)" fill="none" stroke="{}" stroke-width="{}">{}</g>"#,
+                color,
+                stroke,
+                shape.to_svg()
+            )
+        }));
+
+        elements.extend(self.points.iter().map(|(pt, color)| {
+            format!(
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+                pt.x, -pt.y, radius, color
+            )
+        }));
+
+        elements.extend(self.labels.iter().map(|(pt, text)| {
+            format!(
+                r#"<text x="{}" y="{}" font-size="{}">{}</text>"#,
+                pt.x + radius * 1.5,
+                -pt.y - radius * 1.5,
+                font,
+                escape(text)
+            )
+        }));
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\" font-family=\"sans-serif\">\n{}\n</svg>\n",
+            SCENE_WIDTH,
+            (SCENE_WIDTH * height / width).round(),
+            x,
+            -(y + height),
+            width,
+            height,
+            elements.join("\n")
+        )
+    }
+}
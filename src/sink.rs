@@ -0,0 +1,76 @@
+use std::io;
+
+use crate::{
+    stats::{Accumulator, Histogram},
+    test_runner::TestResult,
+};
+
+/// Takes the trials of a run one at a time, see [`crate::test_runner::TestRunner::run_into`]
+pub trait TrialSink {
+    fn push(&mut self, result: &TestResult) -> io::Result<()>;
+}
+
+impl<S: TrialSink + ?Sized> TrialSink for &mut S {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        (**self).push(result)
+    }
+}
+
+/// Skips every trial when there is no sink
+impl<S: TrialSink> TrialSink for Option<S> {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        match self {
+            Some(sink) => sink.push(result),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<A: TrialSink, B: TrialSink> TrialSink for (A, B) {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        self.0.push(result)?;
+        self.1.push(result)
+    }
+}
+
+impl<A: TrialSink, B: TrialSink, C: TrialSink> TrialSink for (A, B, C) {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        self.0.push(result)?;
+        self.1.push(result)?;
+        self.2.push(result)
+    }
+}
+
+/// Keeps every trial, so memory grows with the run
+impl TrialSink for Vec<TestResult> {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        Vec::push(self, result.clone());
+
+        Ok(())
+    }
+}
+
+/// Keeps the delta of every trial, a float a trial
+impl TrialSink for Vec<f64> {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        Vec::push(self, result.delta);
+
+        Ok(())
+    }
+}
+
+impl TrialSink for Accumulator {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        Accumulator::push(self, result.delta);
+
+        Ok(())
+    }
+}
+
+impl TrialSink for Histogram {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        Histogram::push(self, result.delta);
+
+        Ok(())
+    }
+}
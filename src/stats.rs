@@ -0,0 +1,410 @@
+//! NaN values are skipped everywhere, so a lost measurement or failed fix cannot poison a whole
+//! run. Statistics of no values are `None` rather than a panic or a NaN.
+
+/// The values that are not NaN, sorted ascending
+fn sorted(values: &[f64]) -> Vec<f64> {
+    let mut sorted = values
+        .iter()
+        .copied()
+        .filter(|v| !v.is_nan())
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    sorted
+}
+
+/// Quantile `q` of already sorted values, interpolating linearly between neighbours
+fn sorted_quantile(sorted: &[f64], q: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+pub fn mean(values: &[f64]) -> Option<f64> {
+    values.iter().copied().collect::<Accumulator>().mean()
+}
+
+pub fn median(values: &[f64]) -> Option<f64> {
+    quantile(values, 0.5)
+}
+
+/// Sample standard deviation, needs at least 2 values
+pub fn std(values: &[f64]) -> Option<f64> {
+    values.iter().copied().collect::<Accumulator>().std()
+}
+
+/// Value below which a fraction `q` between 0 and 1 of the values fall
+pub fn quantile(values: &[f64], q: f64) -> Option<f64> {
+    sorted_quantile(&sorted(values), q)
+}
+
+/// Several quantiles at once, sorting the values only once
+pub fn quantiles(values: &[f64], qs: &[f64]) -> Option<Vec<f64>> {
+    let sorted = sorted(values);
+
+    qs.iter().map(|q| sorted_quantile(&sorted, *q)).collect()
+}
+
+/// Value below which `p` percent of the values fall
+pub fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    quantile(values, p / 100.0)
+}
+
+/// Root mean square of errors, such as the deltas between predicted and true points
+pub fn rmse(errors: &[f64]) -> Option<f64> {
+    errors.iter().copied().collect::<Accumulator>().rms()
+}
+
+/// Circular error probable, the radius containing a fraction `probability` of the radial errors
+pub fn cep(deltas: &[f64], probability: f64) -> Option<f64> {
+    quantile(deltas, probability)
+}
+
+pub fn cep50(deltas: &[f64]) -> Option<f64> {
+    cep(deltas, 0.5)
+}
+
+pub fn cep95(deltas: &[f64]) -> Option<f64> {
+    cep(deltas, 0.95)
+}
+
+/// Median absolute deviation from the median, unscaled
+pub fn mad(values: &[f64]) -> Option<f64> {
+    let center = median(values)?;
+    let deviations = values
+        .iter()
+        .map(|v| (v - center).abs())
+        .collect::<Vec<_>>();
+
+    median(&deviations)
+}
+
+/// Values more than `threshold` standard deviations away from the mean
+pub fn identify_outliers(values: &[f64], threshold: f64) -> Vec<f64> {
+    let (mean, std) = match (mean(values), std(values)) {
+        (Some(mean), Some(std)) if std > 0.0 => (mean, std),
+        _ => return vec![],
+    };
+
+    values
+        .iter()
+        .copied()
+        .filter(|v| ((v - mean) / std).abs() > threshold)
+        .collect()
+}
+
+/// Running count, mean, variance, extremes and mean square, updated one value at a time with
+/// Welford's algorithm so nothing has to be kept in memory
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Accumulator {
+    pub count: usize,
+    mean: f64,
+    /// Sum of squared deviations from the running mean
+    m2: f64,
+    sum_squares: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Accumulator {
+    pub fn new() -> Self {
+        Accumulator {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            sum_squares: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+
+        self.count += 1;
+
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.sum_squares += value.powi(2);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Combines the values of both accumulators, as if they had all been pushed into one
+    pub fn merge(&mut self, other: &Accumulator) {
+        if other.count == 0 {
+            return;
+        }
+
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+
+        self.m2 += other.m2 + delta.powi(2) * (self.count * other.count) as f64 / count as f64;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.count = count;
+        self.sum_squares += other.sum_squares;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// Sample variance, needs at least 2 values
+    pub fn variance(&self) -> Option<f64> {
+        (self.count > 1).then(|| self.m2 / (self.count - 1) as f64)
+    }
+
+    pub fn std(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+
+    /// Root mean square, the RMSE when the values are errors
+    pub fn rms(&self) -> Option<f64> {
+        (self.count > 0).then(|| (self.sum_squares / self.count as f64).sqrt())
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Accumulator::new()
+    }
+}
+
+impl Extend<f64> for Accumulator {
+    fn extend<T: IntoIterator<Item = f64>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|value| self.push(value));
+    }
+}
+
+impl FromIterator<f64> for Accumulator {
+    fn from_iter<T: IntoIterator<Item = f64>>(iter: T) -> Self {
+        let mut acc = Accumulator::new();
+        acc.extend(iter);
+
+        acc
+    }
+}
+
+/// Counts of values in `bins` equal width bins between `min` and `max`.
+///
+/// Values can be pushed one at a time, so with a range fixed up front it also works as a
+/// streaming estimate of quantiles.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<usize>,
+    /// Values below `min` or above `max`
+    pub outside: usize,
+}
+
+impl Histogram {
+    pub fn new(bins: usize, min: f64, max: f64) -> Self {
+        Histogram {
+            min,
+            max,
+            counts: vec![0; bins.max(1)],
+            outside: 0,
+        }
+    }
+
+    /// Histogram of `values` spanning their own range, `None` when there are none
+    pub fn from_values(values: &[f64], bins: usize) -> Option<Self> {
+        let acc = values.iter().copied().collect::<Accumulator>();
+
+        let mut histogram = Histogram::new(bins, acc.min()?, acc.max()?);
+        values.iter().for_each(|v| histogram.push(*v));
+
+        Some(histogram)
+    }
+
+    pub fn bin_width(&self) -> f64 {
+        (self.max - self.min) / self.counts.len() as f64
+    }
+
+    /// Lower and upper edge of every bin
+    pub fn bin_edges(&self) -> Vec<(f64, f64)> {
+        let width = self.bin_width();
+
+        (0..self.counts.len())
+            .map(|i| {
+                (
+                    self.min + i as f64 * width,
+                    self.min + (i + 1) as f64 * width,
+                )
+            })
+            .collect()
+    }
+
+    /// Values within the range, the upper edge included in the last bin
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    pub fn push(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+
+        if value < self.min || value > self.max {
+            self.outside += 1;
+            return;
+        }
+
+        let bins = self.counts.len();
+        let bin = if self.max > self.min {
+            (((value - self.min) / self.bin_width()) as usize).min(bins - 1)
+        } else {
+            0
+        };
+
+        self.counts[bin] += 1;
+    }
+
+    /// Approximate quantile `q` of the values within the range, assuming they are spread evenly
+    /// inside every bin
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let total = self.total();
+
+        if total == 0 {
+            return None;
+        }
+
+        let target = q.clamp(0.0, 1.0) * total as f64;
+        let mut below = 0.0;
+
+        for ((lower, upper), count) in self.bin_edges().into_iter().zip(&self.counts) {
+            let count = *count as f64;
+
+            if count > 0.0 && below + count >= target {
+                return Some(lower + (upper - lower) * (target - below) / count);
+            }
+
+            below += count;
+        }
+
+        Some(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("Bad missing statistic");
+
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn quantiles_interpolate_and_skip_nan() {
+        let values = [4.0, f64::NAN, 1.0, 3.0, 2.0, 5.0];
+
+        assert_close(median(&values), 3.0);
+        assert_close(quantile(&values, 0.25), 2.0);
+        assert_close(quantile(&values, 0.1), 1.4);
+        assert_close(percentile(&values, 100.0), 5.0);
+        assert_eq!(
+            quantiles(&values, &[0.0, 0.5, 1.0]),
+            Some(vec![1.0, 3.0, 5.0])
+        );
+    }
+
+    #[test]
+    fn statistics_of_no_values_are_none() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(mean(&[f64::NAN]), None);
+        assert_eq!(std(&[1.0]), None);
+        assert_eq!(mad(&[f64::NAN, f64::NAN]), None);
+        assert_eq!(quantiles(&[], &[0.5]), None);
+    }
+
+    #[test]
+    fn mad_ignores_nan_and_outliers() {
+        let values = [1.0, 2.0, f64::NAN, 3.0, 4.0, 100.0];
+
+        assert_close(mad(&values), 1.0);
+        assert_eq!(identify_outliers(&[0.0; 10], 2.0), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn accumulator_matches_batch_statistics() {
+        let values = [2.0, 4.0, 4.0, f64::NAN, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let acc = values.iter().copied().collect::<Accumulator>();
+
+        assert_eq!(acc.count, 8);
+        assert_close(acc.mean(), 5.0);
+        assert_close(acc.variance(), 32.0 / 7.0);
+        assert_close(acc.rms(), (232.0_f64 / 8.0).sqrt());
+        assert_close(acc.min(), 2.0);
+        assert_close(acc.max(), 9.0);
+        assert_close(rmse(&values), (232.0_f64 / 8.0).sqrt());
+    }
+
+    #[test]
+    fn merged_accumulators_match_one() {
+        let values = [1.5, -2.0, 3.25, 8.0, 0.5, 6.0, -1.0];
+        let whole = values.iter().copied().collect::<Accumulator>();
+
+        let mut merged = values[..3].iter().copied().collect::<Accumulator>();
+        merged.merge(&values[3..].iter().copied().collect());
+        merged.merge(&Accumulator::new());
+
+        assert_eq!(merged.count, whole.count);
+        assert_close(merged.mean(), whole.mean().unwrap());
+        assert_close(merged.variance(), whole.variance().unwrap());
+        assert_close(merged.rms(), whole.rms().unwrap());
+        assert_eq!(merged.min(), whole.min());
+        assert_eq!(merged.max(), whole.max());
+    }
+
+    #[test]
+    fn histogram_counts_bins_and_outside() {
+        let mut histogram = Histogram::new(4, 0.0, 4.0);
+
+        for value in [0.0, 0.5, 1.5, 3.9, 4.0, -1.0, 5.0, f64::NAN] {
+            histogram.push(value);
+        }
+
+        assert_eq!(histogram.counts, vec![2, 1, 0, 2]);
+        assert_eq!(histogram.outside, 2);
+        assert_eq!(histogram.total(), 5);
+        assert_eq!(histogram.bin_edges()[1], (1.0, 2.0));
+    }
+
+    #[test]
+    fn histogram_quantiles_approximate_exact_ones() {
+        let values = (0..1000).map(|i| i as f64 / 10.0).collect::<Vec<_>>();
+        let histogram = Histogram::from_values(&values, 100).unwrap();
+
+        assert_eq!(histogram.total(), 1000);
+        assert!((histogram.quantile(0.5).unwrap() - median(&values).unwrap()).abs() < 1.0);
+        assert!((histogram.quantile(0.95).unwrap() - cep95(&values).unwrap()).abs() < 1.0);
+        assert_eq!(Histogram::from_values(&[f64::NAN], 10), None);
+        assert_eq!(Histogram::new(10, 0.0, 1.0).quantile(0.5), None);
+    }
+}
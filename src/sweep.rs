@@ -0,0 +1,234 @@
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::{
+    rectangle::Rectangle,
+    report::{
+        format_csv_value, format_value, markdown_table, ExperimentConfig, ReportFormat, Summary,
+    },
+    test_runner::TestRunner,
+};
+
+/// Columns of [`SweepReport::to_csv`], one row per configuration
+const SWEEP_COLUMNS: [&str; 20] = [
+    "localizer",
+    "mode",
+    "num_of_anchors",
+    "error_margin",
+    "bounds_x",
+    "bounds_y",
+    "bounds_width",
+    "bounds_height",
+    "trials",
+    "seed",
+    "count",
+    "failed",
+    "mean",
+    "median",
+    "std",
+    "rmse",
+    "cep50",
+    "cep95",
+    "max",
+    "outliers",
+];
+
+/// Grid of runner settings to repeat the same experiment at, every combination of them gets run
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    /// Only matter to runners without a `noise` model
+    pub error_margins: Vec<f64>,
+    /// Ignored by a [`crate::placement::AnchorPlacement::Fixed`] placement
+    pub anchor_counts: Vec<i32>,
+    pub bounds: Vec<Rectangle>,
+}
+
+impl Sweep {
+    pub fn new(error_margins: Vec<f64>, anchor_counts: Vec<i32>, bounds: Vec<Rectangle>) -> Self {
+        Sweep {
+            error_margins,
+            anchor_counts,
+            bounds,
+        }
+    }
+
+    /// Every combination, by bounds, then anchor count, then error margin
+    pub fn configurations(&self) -> Vec<(f64, i32, Rectangle)> {
+        self.bounds
+            .iter()
+            .flat_map(|bounds| {
+                self.anchor_counts.iter().flat_map(move |anchors| {
+                    self.error_margins
+                        .iter()
+                        .map(move |margin| (*margin, *anchors, bounds.clone()))
+                })
+            })
+            .collect()
+    }
+
+    /// Runs `runner`, whose callback is called `localizer`, `times` times at every configuration.
+    /// The seed stays the same throughout, so configurations differ by their settings alone. The
+    /// runner is left at the last configuration.
+    pub fn run<R: Rng + SeedableRng>(
+        &self,
+        runner: &mut TestRunner<R>,
+        localizer: &str,
+        times: i32,
+    ) -> SweepReport {
+        let rows = self
+            .configurations()
+            .into_iter()
+            .map(|(error_margin, num_of_anchors, bounds)| {
+                runner.error_margin = error_margin;
+                runner.num_of_anchors = num_of_anchors;
+                runner.set_bounds(bounds);
+
+                let mut deltas = Vec::<f64>::new();
+                runner.run_into(times, &mut deltas).expect("Bad sweep run");
+
+                SweepRow {
+                    config: ExperimentConfig::new(localizer, runner, deltas.len()),
+                    summary: Summary::new(&deltas),
+                }
+            })
+            .collect();
+
+        SweepReport { rows }
+    }
+}
+
+/// Results at one configuration of a [`Sweep`]
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepRow {
+    pub config: ExperimentConfig,
+    pub summary: Summary,
+}
+
+/// Summary statistics of every configuration of a [`Sweep`], in the order they were run
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepReport {
+    pub rows: Vec<SweepRow>,
+}
+
+impl SweepReport {
+    /// Plain text is the CSV table
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_csv(),
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.rows).expect("Bad report serialization")
+    }
+
+    /// One line per configuration with a column per setting and statistic, empty where there
+    /// were too few fixes
+    pub fn to_csv(&self) -> String {
+        let lines = self.rows.iter().map(|row| {
+            let (config, summary) = (&row.config, &row.summary);
+
+            [
+                config.localizer.clone(),
+                config.mode.clone(),
+                config.num_of_anchors.to_string(),
+                config.error_margin.to_string(),
+                config.bounds.pt.x.to_string(),
+                config.bounds.pt.y.to_string(),
+                config.bounds.width.to_string(),
+                config.bounds.height.to_string(),
+                config.trials.to_string(),
+                config.seed.to_string(),
+                summary.count.to_string(),
+                summary.failed.to_string(),
+                format_csv_value(summary.mean),
+                format_csv_value(summary.median),
+                format_csv_value(summary.std),
+                format_csv_value(summary.rmse),
+                format_csv_value(summary.cep50),
+                format_csv_value(summary.cep95),
+                format_csv_value(summary.max),
+                summary.outliers.to_string(),
+            ]
+            .join(",")
+        });
+
+        std::iter::once(SWEEP_COLUMNS.join(","))
+            .chain(lines)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Settings and statistics that vary between configurations
+    pub fn to_markdown(&self) -> String {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                vec![
+                    row.config.num_of_anchors.to_string(),
+                    row.config.error_margin.to_string(),
+                    row.config.bounds.to_string(),
+                    row.summary.count.to_string(),
+                    row.summary.failed.to_string(),
+                    format_value(row.summary.mean),
+                    format_value(row.summary.median),
+                    format_value(row.summary.std),
+                    format_value(row.summary.rmse),
+                    format_value(row.summary.cep95),
+                    format_value(row.summary.max),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        markdown_table(
+            &[
+                "Anchors",
+                "Error Margin",
+                "Bounds",
+                "Fixes",
+                "Failed",
+                "Mean",
+                "Median",
+                "Standard Deviation",
+                "RMSE",
+                "CEP95",
+                "Max",
+            ],
+            &rows,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::multilateration::paper_way;
+
+    use super::*;
+
+    #[test]
+    fn sweep_runs_every_configuration() {
+        let bounds = Rectangle::new(0.0, 0.0, 100.0, 100.0);
+        let sweep = Sweep::new(vec![0.0, 0.05], vec![3, 4], vec![bounds.clone()]);
+        let mut runner = TestRunner::new(3, 0.0, bounds, paper_way);
+        runner.seed = 3;
+
+        let report = sweep.run(&mut runner, "paper_way", 20);
+        let settings = report
+            .rows
+            .iter()
+            .map(|row| (row.config.num_of_anchors, row.config.error_margin))
+            .collect::<Vec<_>>();
+
+        assert_eq!(settings, vec![(3, 0.0), (3, 0.05), (4, 0.0), (4, 0.05)]);
+        assert!(report.rows[2].summary.median.expect("Bad median") < 1e-6);
+        assert!(report.rows[2].summary.median < report.rows[3].summary.median);
+
+        let csv = report.to_csv();
+
+        assert_eq!(csv.lines().count(), 5);
+        assert_eq!(csv.lines().next(), Some(SWEEP_COLUMNS.join(",").as_str()));
+    }
+}
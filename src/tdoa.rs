@@ -0,0 +1,318 @@
+use std::fmt::Display;
+
+use crate::{matrix::Matrix, point::Point};
+
+/// Range differences `d_i - d_ref` observed by a set of anchors relative to a reference anchor
+#[derive(PartialEq, Debug, Clone)]
+pub struct TdoaMeasurement {
+    /// Index of the reference anchor, its own entry in `range_differences` is always `0.0`
+    pub reference: usize,
+    pub range_differences: Vec<f64>,
+}
+
+impl TdoaMeasurement {
+    pub fn new(reference: usize, range_differences: Vec<f64>) -> Self {
+        TdoaMeasurement {
+            reference,
+            range_differences,
+        }
+    }
+
+    /// Builds the measurement from (pseudo) ranges, any offset shared by all ranges cancels out.
+    /// Panics when `reference` is not an index of `ranges`.
+    pub fn from_ranges(reference: usize, ranges: &[f64]) -> Self {
+        let reference_range = *ranges.get(reference).expect("Bad reference index");
+
+        TdoaMeasurement::new(
+            reference,
+            ranges.iter().map(|range| range - reference_range).collect(),
+        )
+    }
+
+    /// Indices of every anchor other than the reference
+    fn others(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.range_differences.len()).filter(move |i| *i != self.reference)
+    }
+
+    /// Returns the range differences a tag at `pt` would produce. Panics when `reference` is not
+    /// an index of `anchors`.
+    pub fn predict(anchors: &[Point], reference: usize, pt: &Point) -> Vec<f64> {
+        let reference_dist = anchors
+            .get(reference)
+            .expect("Bad reference index")
+            .distance_to(pt);
+
+        anchors
+            .iter()
+            .map(|anchor| anchor.distance_to(pt) - reference_dist)
+            .collect()
+    }
+
+    /// Sum of squared differences between the measurement and the prediction for `pt`
+    pub fn residual(&self, anchors: &[Point], pt: &Point) -> f64 {
+        TdoaMeasurement::predict(anchors, self.reference, pt)
+            .iter()
+            .zip(self.range_differences.iter())
+            .map(|(predicted, measured)| (predicted - measured).powi(2))
+            .sum()
+    }
+}
+
+impl Display for TdoaMeasurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let differences_str = self
+            .range_differences
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        write!(f, "ref #{}: {}", self.reference, differences_str)
+    }
+}
+
+/// Solver of range differences, `None` when it finds no fix
+pub type TdoaCallback = fn(&[Point], &TdoaMeasurement) -> Option<Point>;
+
+/// Closed form hyperbolic solution in the style of Chan's method.
+///
+/// The linearised equations give the position as `u + v * r_ref`, where `r_ref` is the unknown
+/// distance to the reference anchor, which is then recovered from the quadratic
+/// `r_ref^2 = |u + v * r_ref - anchor_ref|^2`. Needs at least 3 non collinear anchors.
+pub fn chan(anchors: &[Point], measurement: &TdoaMeasurement) -> Option<Point> {
+    let n = anchors.len();
+
+    if n < 3 || measurement.range_differences.len() != n || measurement.reference >= n {
+        return None;
+    }
+
+    let reference = &anchors[measurement.reference];
+    let reference_k = reference.pow(2).sum();
+
+    let mut a = Matrix::new(n - 1, 2);
+    let mut p = Matrix::new(n - 1, 1);
+    let mut q = Matrix::new(n - 1, 1);
+
+    for (row, i) in measurement.others().enumerate() {
+        let r = measurement.range_differences[i];
+
+        a.set(row, 0, anchors[i].x - reference.x)?;
+        a.set(row, 1, anchors[i].y - reference.y)?;
+
+        p.set(
+            row,
+            0,
+            (anchors[i].pow(2).sum() - reference_k - r.powi(2)) / 2.0,
+        )?;
+        q.set(row, 0, -r)?;
+    }
+
+    let a_t = a.transpose();
+    let pseudo_inverse = a_t.dot(&a)?.invert()?.dot(&a_t)?;
+
+    let u = pseudo_inverse.dot(&p)?;
+    let v = pseudo_inverse.dot(&q)?;
+
+    let u = Point::new(*u.get(0, 0)?, *u.get(1, 0)?);
+    let v = Point::new(*v.get(0, 0)?, *v.get(1, 0)?);
+    let w = &u - reference;
+
+    // (v.v - 1) r^2 + 2 (v.w) r + w.w = 0
+    let qa = v.pow(2).sum() - 1.0;
+    let qb = 2.0 * (&v * &w).sum();
+    let qc = w.pow(2).sum();
+
+    let roots = if qa.abs() < f64::EPSILON {
+        vec![-qc / qb]
+    } else {
+        // Noise can push the discriminant below zero, the closest real solution is the vertex
+        let discriminant = (qb.powi(2) - 4.0 * qa * qc).max(0.0).sqrt();
+
+        vec![
+            (-qb + discriminant) / (2.0 * qa),
+            (-qb - discriminant) / (2.0 * qa),
+        ]
+    };
+
+    // A negative distance is not physical, but with enough noise it can be the only solution left
+    let valid_roots = roots
+        .iter()
+        .copied()
+        .filter(|r| r.is_finite() && *r >= 0.0)
+        .collect::<Vec<_>>();
+    let roots = if valid_roots.is_empty() {
+        roots.into_iter().filter(|r| r.is_finite()).collect()
+    } else {
+        valid_roots
+    };
+
+    roots.into_iter().map(|r| &u + &(&v * r)).min_by(|a, b| {
+        measurement
+            .residual(anchors, a)
+            .total_cmp(&measurement.residual(anchors, b))
+    })
+}
+
+/// Iteratively refines `initial` with the Taylor series (Gauss-Newton) method
+pub fn taylor(
+    anchors: &[Point],
+    measurement: &TdoaMeasurement,
+    initial: &Point,
+    max_iterations: usize,
+) -> Option<Point> {
+    let n = anchors.len();
+
+    if n < 3 || measurement.range_differences.len() != n || measurement.reference >= n {
+        return None;
+    }
+
+    let reference = &anchors[measurement.reference];
+    let mut pt = initial.clone();
+
+    for _ in 0..max_iterations {
+        let mut jacobian = Matrix::new(n - 1, 2);
+        let mut residuals = Matrix::new(n - 1, 1);
+
+        let reference_dist = reference.distance_to(&pt);
+        let reference_unit = &(&pt - reference) / reference_dist;
+
+        for (row, i) in measurement.others().enumerate() {
+            let dist = anchors[i].distance_to(&pt);
+            let unit = &(&pt - &anchors[i]) / dist;
+            let gradient = &unit - &reference_unit;
+
+            jacobian.set(row, 0, gradient.x)?;
+            jacobian.set(row, 1, gradient.y)?;
+            residuals.set(
+                row,
+                0,
+                measurement.range_differences[i] - (dist - reference_dist),
+            )?;
+        }
+
+        let jacobian_t = jacobian.transpose();
+        let step = jacobian_t
+            .dot(&jacobian)?
+            .invert()?
+            .dot(&jacobian_t)?
+            .dot(&residuals)?;
+        let step = Point::new(*step.get(0, 0)?, *step.get(1, 0)?);
+
+        pt = &pt + &step;
+
+        if !(pt.x.is_finite() && pt.y.is_finite()) {
+            return None;
+        }
+
+        if step.pow(2).sum().sqrt() < 1e-9 {
+            break;
+        }
+    }
+
+    Some(pt)
+}
+
+/// Chan's closed form estimate polished by a few Taylor series iterations
+pub fn chan_taylor(anchors: &[Point], measurement: &TdoaMeasurement) -> Option<Point> {
+    let initial = chan(anchors, measurement)?;
+
+    taylor(anchors, measurement, &initial, 10).or(Some(initial))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Option<Point>, expected: &Point) {
+        let actual = actual.expect("Bad missing fix");
+
+        assert!(
+            actual.distance_to(expected) < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn anchors(n: usize) -> Vec<Point> {
+        [
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(0.0, 100.0),
+            Point::new(100.0, 100.0),
+            Point::new(50.0, 150.0),
+        ][..n]
+            .to_vec()
+    }
+
+    fn exact(anchors: &[Point], reference: usize, tag: &Point) -> TdoaMeasurement {
+        TdoaMeasurement::new(reference, TdoaMeasurement::predict(anchors, reference, tag))
+    }
+
+    #[test]
+    fn chan_recovers_the_tag() {
+        let tag = Point::new(30.0, 40.0);
+
+        for n in [3, 5] {
+            let anchors = anchors(n);
+
+            assert_near(chan(&anchors, &exact(&anchors, 0, &tag)), &tag);
+            assert_near(chan(&anchors, &exact(&anchors, 2, &tag)), &tag);
+        }
+    }
+
+    #[test]
+    fn taylor_recovers_the_tag() {
+        let tag = Point::new(60.0, 45.0);
+        let initial = Point::new(50.0, 50.0);
+
+        for n in [3, 5] {
+            let anchors = anchors(n);
+            let measurement = exact(&anchors, 0, &tag);
+
+            assert_near(taylor(&anchors, &measurement, &initial, 20), &tag);
+            assert_near(chan_taylor(&anchors, &measurement), &tag);
+        }
+    }
+
+    #[test]
+    fn from_ranges_cancels_a_shared_offset() {
+        let anchors = anchors(5);
+        let tag = Point::new(20.0, 80.0);
+        let ranges = anchors
+            .iter()
+            .map(|anchor| anchor.distance_to(&tag) + 25.0)
+            .collect::<Vec<_>>();
+
+        assert_near(
+            chan_taylor(&anchors, &TdoaMeasurement::from_ranges(0, &ranges)),
+            &tag,
+        );
+    }
+
+    #[test]
+    fn collinear_or_too_few_anchors_have_no_fix() {
+        let anchors = [
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(20.0, 0.0),
+        ];
+        let measurement = exact(&anchors, 0, &Point::new(5.0, 5.0));
+
+        assert_eq!(chan(&anchors, &measurement), None);
+        assert_eq!(chan_taylor(&anchors, &measurement), None);
+        assert_eq!(chan(&anchors[..2], &measurement), None);
+    }
+
+    #[test]
+    fn reference_past_the_anchors_has_no_fix() {
+        let anchors = anchors(3);
+        let measurement = TdoaMeasurement::new(3, vec![0.0; 3]);
+
+        assert_eq!(chan(&anchors, &measurement), None);
+        assert_eq!(
+            taylor(&anchors, &measurement, &Point::new(50.0, 50.0), 10),
+            None
+        );
+    }
+}
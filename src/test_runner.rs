@@ -1,22 +1,180 @@
-use std::fmt::Display;
+use std::{
+    convert::Infallible,
+    fmt::Display,
+    io,
+    marker::PhantomData,
+    panic,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
 
-use rand::{distributions::Uniform, rngs::ThreadRng, Rng};
+use rand::{distributions::Uniform, rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Normal;
 
-use crate::{point::Point, rectangle::Rectangle};
+use crate::{
+    aoa::{self, AoaCallback, AoaMeasurement, HybridCallback},
+    comparison::Comparison,
+    heatmap::Heatmap,
+    kalman::Tracker,
+    localizer::{Localizer, SolveError},
+    matrix::Matrix,
+    noise::{Multiplicative, NoiseModel},
+    placement::AnchorPlacement,
+    point::Point,
+    progress::{NoProgress, ProgressObserver},
+    rectangle::Rectangle,
+    rssi::{PathLossModel, RssiMeasurement},
+    sink::TrialSink,
+    tdoa::{TdoaCallback, TdoaMeasurement},
+    trajectory::{TimedPoint, Trajectory},
+    two_dim_shape::Contains,
+    uncertainty::{self, Uncertainty},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TestResult {
+    /// Seed of the run, see [`TestRunner::run_iteration`] for regenerating this result
+    pub seed: u64,
+    pub iteration: u64,
     pub tag_pt: Point,
     pub anchor_pts: Vec<Point>,
     pub real_distances: Vec<f64>,
     pub distance_coefficients: Vec<f64>,
     pub adjusted_distances: Vec<f64>,
+    /// Anchors whose measurement was lost, their adjusted distance and coefficient are NaN. The
+    /// solver, `tdoa` and `aoa` only cover the remaining anchors.
+    pub dropped: Vec<usize>,
+    /// Range differences handed to the solver when running in [`SimulationMode::Tdoa`]
+    pub tdoa: Option<TdoaMeasurement>,
+    /// Noisy bearings handed to the solver when running in [`SimulationMode::Aoa`] or
+    /// [`SimulationMode::Hybrid`]
+    pub aoa: Option<AoaMeasurement>,
+    /// RSSI readings the `adjusted_distances` were derived from in [`SimulationMode::Rssi`]
+    pub rssi: Option<RssiMeasurement>,
+    /// NaN when the trial got no fix, see `failure`
     pub predicted_pt: Point,
     pub delta: f64,
+    /// Predicted accuracy of `predicted_pt`, `None` when the geometry is degenerate
+    pub uncertainty: Option<Uncertainty>,
+    /// Why the trial got no fix, `None` when it did
+    pub failure: Option<SolveError>,
+}
+
+impl TestResult {
+    /// The anchors and adjusted distances of the measurements that were not dropped
+    pub fn received(&self) -> (Vec<Point>, Vec<f64>) {
+        (0..self.anchor_pts.len())
+            .filter(|i| !self.dropped.contains(i))
+            .map(|i| (self.anchor_pts[i].clone(), self.adjusted_distances[i]))
+            .unzip()
+    }
+
+    /// Describes the kind of measurements the trial took
+    pub fn measurements(&self) -> &'static str {
+        match (&self.tdoa, &self.aoa, &self.rssi) {
+            (Some(_), _, _) => "range differences",
+            (_, Some(_), _) => "ranges and bearings",
+            (_, _, Some(_)) => "RSSI ranges",
+            _ => "ranges",
+        }
+    }
+
+    /// Spreads `values`, which only cover the anchors that were not dropped, over every anchor
+    /// with NaN for the dropped ones
+    fn per_anchor(&self, mut values: impl Iterator<Item = f64>) -> Vec<f64> {
+        (0..self.anchor_pts.len())
+            .map(|i| {
+                if self.dropped.contains(&i) {
+                    f64::NAN
+                } else {
+                    values.next().unwrap_or(f64::NAN)
+                }
+            })
+            .collect()
+    }
+
+    /// Adjusted minus predicted distance of every anchor for a tag at `predicted_pt`, NaN for the
+    /// dropped ones
+    pub fn range_residuals(&self) -> Vec<f64> {
+        self.anchor_pts
+            .iter()
+            .zip(&self.adjusted_distances)
+            .map(|(anchor, dist)| dist - anchor.distance_to(&self.predicted_pt))
+            .collect()
+    }
+
+    /// Measured minus predicted range difference of every anchor, `None` without a TDoA
+    /// measurement
+    pub fn range_difference_residuals(&self) -> Option<Vec<f64>> {
+        let tdoa = self.tdoa.as_ref()?;
+        let (received_pts, _) = self.received();
+        let predicted = TdoaMeasurement::predict(&received_pts, tdoa.reference, &self.predicted_pt);
+
+        Some(
+            self.per_anchor(
+                tdoa.range_differences
+                    .iter()
+                    .zip(predicted)
+                    .map(|(measured, predicted)| measured - predicted),
+            ),
+        )
+    }
+
+    /// Measured minus predicted bearing of every anchor in radians, `None` without bearings
+    pub fn bearing_residuals(&self) -> Option<Vec<f64>> {
+        let aoa = self.aoa.as_ref()?;
+        let (received_pts, _) = self.received();
+
+        Some(
+            self.per_anchor(
+                aoa.bearings
+                    .iter()
+                    .zip(AoaMeasurement::predict(&received_pts, &self.predicted_pt))
+                    .map(|(measured, predicted)| aoa::wrap_angle(measured - predicted)),
+            ),
+        )
+    }
+
+    /// Sets `predicted_pt`, `delta` and `failure` from the outcome of a solver, a fix that is not
+    /// finite counts as none at all
+    pub fn set_fix(&mut self, fix: Result<Point, SolveError>) {
+        let fix = fix.and_then(|pt| {
+            if pt.x.is_finite() && pt.y.is_finite() {
+                Ok(pt)
+            } else {
+                Err(SolveError::NoFix)
+            }
+        });
+
+        match fix {
+            Ok(pt) => {
+                self.delta = self.tag_pt.distance_to(&pt);
+                self.predicted_pt = pt;
+                self.failure = None;
+            }
+            Err(err) => {
+                self.predicted_pt = Point::new(f64::NAN, f64::NAN);
+                self.delta = f64::NAN;
+                self.failure = Some(err);
+            }
+        }
+    }
+
+    /// Whether the predicted error ellipse contains the true tag point
+    pub fn is_covered(&self) -> bool {
+        self.uncertainty
+            .as_ref()
+            .is_some_and(|uncertainty| uncertainty.ellipse.contains(&self.tag_pt))
+    }
 }
 
 impl Display for TestResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Seed: {}, Iteration: {}", self.seed, self.iteration)?;
         writeln!(f, "Tag Pt: {}", self.tag_pt)?;
 
         let anchors_str = self
@@ -51,21 +209,169 @@ impl Display for TestResult {
             .join(", ");
         writeln!(f, "Adjusted Distances: {}", adjusted_str)?;
 
+        if !self.dropped.is_empty() {
+            let dropped_str = self
+                .dropped
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            writeln!(f, "Dropped: {}", dropped_str)?;
+        }
+
+        if let Some(tdoa) = &self.tdoa {
+            writeln!(f, "TDoA: {}", tdoa)?;
+        }
+
+        if let Some(aoa) = &self.aoa {
+            writeln!(f, "AoA: {}", aoa)?;
+        }
+
+        if let Some(rssi) = &self.rssi {
+            writeln!(f, "RSSI: {}", rssi)?;
+        }
+
         writeln!(f, "Predicted Pt: {}", self.predicted_pt)?;
-        writeln!(f, "Delta: {}", self.delta)
+        writeln!(f, "Delta: {}", self.delta)?;
+
+        if let Some(failure) = &self.failure {
+            writeln!(f, "Failure: {}", failure)?;
+        }
+
+        match &self.uncertainty {
+            Some(uncertainty) => writeln!(f, "Uncertainty: {}", uncertainty),
+            None => writeln!(f, "Uncertainty: None"),
+        }
+    }
+}
+
+/// One time step of [`TestRunner::run_path`]
+#[derive(Debug)]
+pub struct TrackResult {
+    pub time: f64,
+    pub tag_pt: Point,
+    pub adjusted_distances: Vec<f64>,
+    /// Independent fix of the runner's `callback` from this step's distances alone, NaN when
+    /// too few of them were received or the callback found no fix
+    pub snapshot_pt: Point,
+    pub snapshot_delta: f64,
+    pub filtered_pt: Point,
+    pub delta: f64,
+}
+
+impl Display for TrackResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "t={}: Tag Pt: {}, Snapshot Pt: {} ({}), Filtered Pt: {} ({})",
+            self.time,
+            self.tag_pt,
+            self.snapshot_pt,
+            self.snapshot_delta,
+            self.filtered_pt,
+            self.delta
+        )
+    }
+}
+
+/// Time series of true and filtered positions for one set of fixed anchors
+#[derive(Debug)]
+pub struct TrajectoryResult {
+    pub anchor_pts: Vec<Point>,
+    pub steps: Vec<TrackResult>,
+}
+
+/// Solver of absolute distances, `None` when it finds no fix
+pub type TestRunnerCallback = fn(&[Point], &[f64]) -> Option<Point>;
+
+/// What the simulated anchors measure
+#[derive(Debug, Clone)]
+pub enum SimulationMode {
+    /// Absolute distances, solved by the runner's `callback`
+    Range,
+    /// Range differences relative to the `reference` anchor.
+    ///
+    /// Each anchor's clock is offset from the reference by a normally distributed amount with a
+    /// standard deviation of `clock_offset_std`, expressed as a distance (offset * signal speed).
+    Tdoa {
+        reference: usize,
+        clock_offset_std: f64,
+        callback: TdoaCallback,
+    },
+    /// Bearings only, each disturbed by normally distributed noise with `angle_std` radians
+    Aoa {
+        angle_std: f64,
+        callback: AoaCallback,
+    },
+    /// Both the usual noisy distances and bearings with `angle_std` radians of noise
+    Hybrid {
+        angle_std: f64,
+        callback: HybridCallback,
+    },
+    /// RSSI readings generated by `model` instead of the range noise, converted back into ranges
+    /// for the runner's `callback`
+    Rssi { model: PathLossModel },
+}
+
+impl SimulationMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SimulationMode::Range => "range",
+            SimulationMode::Tdoa { .. } => "tdoa",
+            SimulationMode::Aoa { .. } => "aoa",
+            SimulationMode::Hybrid { .. } => "hybrid",
+            SimulationMode::Rssi { .. } => "rssi",
+        }
+    }
+
+    /// Fewest anchors the measurements have to come from for the solvers of this mode to fix the
+    /// tag, trials with fewer anchors left after dropouts get no fix
+    pub fn min_anchors(&self) -> usize {
+        match self {
+            SimulationMode::Range | SimulationMode::Tdoa { .. } | SimulationMode::Rssi { .. } => 3,
+            SimulationMode::Aoa { .. } => 2,
+            SimulationMode::Hybrid { .. } => 1,
+        }
     }
 }
 
-pub type TestRunnerCallback = fn(&Vec<Point>, &Vec<f64>) -> Point;
+/// SplitMix64 finaliser, spreads consecutive iterations over unrelated generator seeds
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
 
+    z ^ (z >> 31)
+}
+
+/// Trials [`TestRunner::run_into`] holds in memory at a time
+pub const TRIAL_BATCH: usize = 16384;
+
+/// Runs simulated trials with random generator `R`.
+///
+/// Every iteration draws from its own generator, seeded from `seed` and the iteration index, so a
+/// run is reproducible and any single trial can be regenerated without replaying the ones before.
 #[derive(Debug)]
-pub struct TestRunner {
+pub struct TestRunner<R = StdRng> {
     pub num_of_anchors: i32,
     pub error_margin: f64,
     pub callback: TestRunnerCallback,
+    pub mode: SimulationMode,
+    /// How the `num_of_anchors` anchors are laid out, a [`AnchorPlacement::Fixed`] list is used
+    /// whole whatever its length
+    pub placement: AnchorPlacement,
+    /// Range noise, `dist + dist * U(-error_margin, error_margin)` when not set
+    pub noise: Option<Box<dyn NoiseModel>>,
+    /// Random by default, set it to repeat an earlier run
+    pub seed: u64,
+    /// Worker threads `run` splits the iterations across, the results do not depend on it
+    pub threads: usize,
+    /// Told how far runs are and able to cancel them, [`NoProgress`] by default
+    pub progress: Box<dyn ProgressObserver>,
+    bounds: Rectangle,
     x_range: Uniform<f64>,
     y_range: Uniform<f64>,
-    rnd: ThreadRng,
+    // Generators are only created inside the workers, so the runner is `Sync` whatever `R` is
+    rng: PhantomData<fn() -> R>,
 }
 
 impl TestRunner {
@@ -74,83 +380,646 @@ impl TestRunner {
         error_margin: f64,
         bounds: Rectangle,
         callback: TestRunnerCallback,
+    ) -> Self {
+        TestRunner::with_rng(num_of_anchors, error_margin, bounds, callback)
+    }
+}
+
+impl<R: Rng + SeedableRng> TestRunner<R> {
+    /// Like [`TestRunner::new`], drawing from `R` instead of [`StdRng`]
+    pub fn with_rng(
+        num_of_anchors: i32,
+        error_margin: f64,
+        bounds: Rectangle,
+        callback: TestRunnerCallback,
     ) -> Self {
         TestRunner {
             num_of_anchors,
             error_margin,
             callback,
+            mode: SimulationMode::Range,
+            placement: AnchorPlacement::Random,
+            noise: None,
+            seed: rand::thread_rng().gen(),
+            threads: 1,
+            progress: Box::new(NoProgress),
             x_range: Uniform::from(bounds.x_range()),
             y_range: Uniform::from(bounds.y_range()),
-            rnd: rand::thread_rng(),
+            bounds,
+            rng: PhantomData,
         }
     }
 
-    fn rand_pt(&mut self) -> Point {
-        Point::new(
-            self.rnd.sample(&self.x_range),
-            self.rnd.sample(&self.y_range),
+    pub fn bounds(&self) -> &Rectangle {
+        &self.bounds
+    }
+
+    /// Anchors every trial has, the whole list of a [`AnchorPlacement::Fixed`] placement
+    pub fn anchor_count(&self) -> usize {
+        match &self.placement {
+            AnchorPlacement::Fixed(points) => points.len(),
+            _ => self.num_of_anchors.max(0) as usize,
+        }
+    }
+
+    /// Moves the anchors and tags of the following runs to `bounds`
+    pub fn set_bounds(&mut self, bounds: Rectangle) {
+        self.x_range = Uniform::from(bounds.x_range());
+        self.y_range = Uniform::from(bounds.y_range());
+        self.bounds = bounds;
+    }
+
+    /// Generator of the given iteration of the run
+    pub fn iteration_rng(&self, iteration: u64) -> R {
+        R::seed_from_u64(mix(self.seed ^ mix(iteration)))
+    }
+
+    fn rand_pt(&self, rng: &mut R) -> Point {
+        Point::new(rng.sample(self.x_range), rng.sample(self.y_range))
+    }
+
+    fn noisy_bearings(
+        &self,
+        rng: &mut R,
+        anchors: &[Point],
+        tag: &Point,
+        angle_std: f64,
+    ) -> AoaMeasurement {
+        let noise = Normal::new(0.0, angle_std).expect("Bad angle standard deviation");
+
+        AoaMeasurement::new(
+            anchors
+                .iter()
+                .map(|anchor| aoa::wrap_angle(aoa::bearing(anchor, tag) + rng.sample(noise)))
+                .collect(),
         )
     }
 
+    /// Measured distance for `dist`, `None` when the noise model drops it
+    fn noisy_distance(&self, rng: &mut R, dist: f64) -> Option<f64> {
+        match &self.noise {
+            Some(noise) => noise.apply(dist, rng),
+            None => Multiplicative::new(self.error_margin).apply(dist, rng),
+        }
+    }
+
+    fn range_variance(&self, dist: f64) -> f64 {
+        match &self.noise {
+            Some(noise) => noise.variance(dist),
+            None => Multiplicative::new(self.error_margin).variance(dist),
+        }
+    }
+
+    /// Covariance of an estimate at `pt`, from the measurement Jacobian and this runner's noise.
+    /// In [`SimulationMode::Tdoa`] the differences are taken relative to `anchors[reference]`.
+    fn uncertainty(&self, anchors: &[Point], pt: &Point, reference: usize) -> Option<Uncertainty> {
+        let n = anchors.len();
+
+        let range_variances = anchors
+            .iter()
+            .map(|anchor| self.range_variance(anchor.distance_to(pt)))
+            .collect::<Vec<_>>();
+
+        let covariance = match self.mode {
+            SimulationMode::Range => uncertainty::diagonal_covariance(
+                &uncertainty::range_jacobian(anchors, pt),
+                &range_variances,
+            ),
+            SimulationMode::Tdoa {
+                clock_offset_std, ..
+            } => {
+                // Every difference shares the reference anchor's error
+                let others = (0..n).filter(|i| *i != reference).collect::<Vec<_>>();
+                let mut noise = Matrix::new(n - 1, n - 1);
+
+                for (row, i) in others.iter().enumerate() {
+                    for (col, j) in others.iter().enumerate() {
+                        let own = if i == j {
+                            range_variances[*i] + clock_offset_std.powi(2)
+                        } else {
+                            0.0
+                        };
+
+                        noise.set(row, col, range_variances[reference] + own)?;
+                    }
+                }
+
+                uncertainty::covariance(&uncertainty::tdoa_jacobian(anchors, reference, pt), &noise)
+            }
+            SimulationMode::Aoa { angle_std, .. } => uncertainty::diagonal_covariance(
+                &uncertainty::bearing_jacobian(anchors, pt),
+                &vec![angle_std.powi(2); n],
+            ),
+            SimulationMode::Hybrid { angle_std, .. } => uncertainty::diagonal_covariance(
+                &uncertainty::stack(
+                    &uncertainty::range_jacobian(anchors, pt),
+                    &uncertainty::bearing_jacobian(anchors, pt),
+                )?,
+                &range_variances
+                    .iter()
+                    .copied()
+                    .chain(vec![angle_std.powi(2); n])
+                    .collect::<Vec<_>>(),
+            ),
+            SimulationMode::Rssi { model } => {
+                // First order propagation of the shadowing through `PathLossModel::range`
+                let variances = anchors
+                    .iter()
+                    .map(|anchor| {
+                        (anchor.distance_to(pt) * 10f64.ln() * model.shadowing_std
+                            / (10.0 * model.exponent))
+                            .powi(2)
+                    })
+                    .collect::<Vec<_>>();
+
+                uncertainty::diagonal_covariance(
+                    &uncertainty::range_jacobian(anchors, pt),
+                    &variances,
+                )
+            }
+        }?;
+
+        Uncertainty::new(anchors, pt, covariance)
+    }
+
+    /// Runs `times` trials, fewer when the `progress` observer cancels the run. Every result is
+    /// kept, see [`TestRunner::run_into`] for long runs.
     pub fn run(&mut self, times: i32) -> Vec<TestResult> {
-        let mut tag_pt = self.rand_pt();
-        let mut anchor_pts = (0..self.num_of_anchors)
+        self.run_parallel(times.max(0) as usize, |iteration| {
+            self.run_iteration(iteration)
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// The trials of a run, each generated on the calling thread as it is asked for. They are the
+    /// same as the ones of [`TestRunner::run`] with the same seed.
+    pub fn trials(&self, times: u64) -> impl Iterator<Item = TestResult> + '_ {
+        (0..times).map(|iteration| self.run_iteration(iteration))
+    }
+
+    /// Runs `times` trials over the `threads` workers like [`TestRunner::run`], handing them to
+    /// `sink` in iteration order. Only [`TRIAL_BATCH`] trials are held at a time, so memory use
+    /// does not grow with the run. Returns the trials run, fewer when the `progress` observer
+    /// cancels the run, and stops at the first error of the sink.
+    pub fn run_into(&self, times: i32, sink: &mut impl TrialSink) -> io::Result<usize> {
+        let mut count = 0;
+
+        self.run_batched(
+            times.max(0) as usize,
+            TRIAL_BATCH,
+            |iteration| self.run_iteration(iteration),
+            |batch| {
+                for result in batch.into_iter().flatten() {
+                    sink.push(&result)?;
+                    count += 1;
+                }
+
+                Ok::<(), io::Error>(())
+            },
+        )?;
+
+        Ok(count)
+    }
+
+    /// Calls `trial` for every iteration below `times` over the `threads` workers, see
+    /// [`TestRunner::run_batched`], and collects the results
+    fn run_parallel<T: Send>(
+        &self,
+        times: usize,
+        trial: impl Fn(u64) -> T + Sync,
+    ) -> Vec<Option<T>> {
+        let mut results = Vec::with_capacity(times);
+
+        let Ok(()) = self.run_batched(times, times, trial, |batch| {
+            results.extend(batch);
+
+            Ok::<(), Infallible>(())
+        });
+
+        results
+    }
+
+    /// Calls `trial` for every iteration below `times`, `batch` iterations at a time, each batch
+    /// split into contiguous blocks over `threads` workers and handed to `consume` in iteration
+    /// order once they are all done. The `progress` observer is kept posted throughout. When it
+    /// cancels the run, the iterations left in that batch are `None` and no further batch runs.
+    fn run_batched<T: Send, E>(
+        &self,
+        times: usize,
+        batch: usize,
+        trial: impl Fn(u64) -> T + Sync,
+        mut consume: impl FnMut(Vec<Option<T>>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let batch = batch.max(1);
+        let done = AtomicUsize::new(0);
+        let cancelled = AtomicBool::new(false);
+
+        self.progress.start(times);
+
+        for start in (0..times).step_by(batch) {
+            let end = (start + batch).min(times);
+            let threads = self.threads.clamp(1, end - start);
+            let block = (end - start).div_ceil(threads);
+
+            let results = thread::scope(|scope| {
+                // Never sent on, the receiver only learns that every worker has dropped its sender
+                let (running, all_finished) = mpsc::channel::<()>();
+
+                let workers = (0..threads)
+                    .map(|worker| {
+                        let (trial, done, cancelled) = (&trial, &done, &cancelled);
+                        let running = running.clone();
+                        let iterations =
+                            (start + worker * block)..(start + (worker + 1) * block).min(end);
+
+                        scope.spawn(move || {
+                            let _running = running;
+
+                            iterations
+                                .map(|iteration| {
+                                    if cancelled.load(Ordering::Relaxed) {
+                                        return None;
+                                    }
+
+                                    let result = trial(iteration as u64);
+
+                                    done.fetch_add(1, Ordering::Relaxed);
+
+                                    Some(result)
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                drop(running);
+
+                // Wakes up as soon as the batch is done, or every 100ms until then to post progress
+                while let Err(RecvTimeoutError::Timeout) =
+                    all_finished.recv_timeout(Duration::from_millis(100))
+                {
+                    if !self.progress.update(done.load(Ordering::Relaxed), times) {
+                        cancelled.store(true, Ordering::Relaxed);
+                    }
+                }
+
+                // Joined in order, so the results come out in iteration order
+                workers
+                    .into_iter()
+                    .flat_map(|worker| {
+                        worker
+                            .join()
+                            .unwrap_or_else(|err| panic::resume_unwind(err))
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            if !cancelled.load(Ordering::Relaxed)
+                && !self.progress.update(done.load(Ordering::Relaxed), times)
+            {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+
+            if let Err(err) = consume(results) {
+                self.progress.finish(done.load(Ordering::Relaxed), times);
+
+                return Err(err);
+            }
+
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+
+        self.progress.finish(done.into_inner(), times);
+
+        Ok(())
+    }
+
+    /// Runs a single trial, the same `seed` and `iteration` always give the same result
+    pub fn run_iteration(&self, iteration: u64) -> TestResult {
+        let mut rng = self.iteration_rng(iteration);
+        let tag_pt = self.rand_pt(&mut rng);
+
+        self.trial(iteration, &mut rng, tag_pt)
+    }
+
+    /// Like [`TestRunner::run_iteration`] with the tag at `tag_pt` instead of a random point
+    pub fn run_iteration_at(&self, iteration: u64, tag_pt: &Point) -> TestResult {
+        self.trial(
+            iteration,
+            &mut self.iteration_rng(iteration),
+            tag_pt.clone(),
+        )
+    }
+
+    /// Grids the bounds into `cols x rows` cells and runs `trials` trials with the tag at the
+    /// centre of every cell. Use a placement other than [`AnchorPlacement::Random`] to map a
+    /// single anchor layout. Trials a cancelled run never got to count as failed.
+    pub fn run_heatmap(&self, cols: usize, rows: usize, trials: usize) -> Heatmap {
+        let centers = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .map(|(row, col)| Heatmap::cell_center(&self.bounds, cols, rows, row, col))
+            .collect::<Vec<_>>();
+
+        let deltas = self
+            .run_parallel(centers.len() * trials, |iteration| {
+                let cell = iteration as usize / trials;
+
+                self.run_iteration_at(iteration, &centers[cell]).delta
+            })
             .into_iter()
-            .map(|_| self.rand_pt())
+            .map(|delta| delta.unwrap_or(f64::NAN))
+            .collect::<Vec<_>>();
+
+        Heatmap::new(self.bounds.clone(), cols, rows, &deltas)
+    }
+
+    /// Places the anchors and takes the measurements of a trial, leaving the fix to the caller:
+    /// `predicted_pt` and `delta` are NaN and `uncertainty` is `None`
+    fn measure(&self, iteration: u64, rng: &mut R, tag_pt: Point) -> TestResult {
+        let anchor_pts = self.placement.place(&self.bounds, self.anchor_count(), rng);
+
+        let distances = anchor_pts
+            .iter()
+            .map(|pt| pt.distance_to(&tag_pt))
             .collect::<Vec<_>>();
 
-        (1..=times)
+        let mut tdoa = None;
+        let mut aoa = None;
+        let mut rssi = None;
+
+        let mut adjusted_distances = match self.mode {
+            SimulationMode::Rssi { model } => {
+                let shadowing = Normal::new(0.0, model.shadowing_std)
+                    .expect("Bad shadowing standard deviation");
+                let measurement = rssi.insert(RssiMeasurement::new(
+                    distances
+                        .iter()
+                        .map(|dist| model.rssi(*dist) + rng.sample(shadowing))
+                        .collect(),
+                ));
+
+                measurement.ranges(&model)
+            }
+            _ => distances
+                .iter()
+                .map(|dist| self.noisy_distance(rng, *dist).unwrap_or(f64::NAN))
+                .collect(),
+        };
+
+        if let SimulationMode::Tdoa {
+            reference,
+            clock_offset_std,
+            ..
+        } = self.mode
+        {
+            let clock_offset =
+                Normal::new(0.0, clock_offset_std).expect("Bad clock offset standard deviation");
+
+            for (index, dist) in adjusted_distances.iter_mut().enumerate() {
+                if index != reference {
+                    *dist += rng.sample(clock_offset);
+                }
+            }
+        }
+
+        let distance_coefficients = adjusted_distances
+            .iter()
+            .zip(distances.iter())
+            .map(|(adjusted, dist)| adjusted / dist - 1.0)
+            .collect::<Vec<_>>();
+
+        let (dropped, received): (Vec<usize>, Vec<usize>) =
+            (0..anchor_pts.len()).partition(|i| adjusted_distances[*i].is_nan());
+        let received_pts = received
+            .iter()
+            .map(|i| anchor_pts[*i].clone())
+            .collect::<Vec<_>>();
+        let received_distances = received
+            .iter()
+            .map(|i| adjusted_distances[*i])
+            .collect::<Vec<_>>();
+
+        match self.mode {
+            SimulationMode::Tdoa { reference, .. } => {
+                // The first remaining anchor stands in for a TDoA reference that was dropped
+                let reference = received.iter().position(|i| *i == reference).unwrap_or(0);
+
+                tdoa = Some(TdoaMeasurement::from_ranges(reference, &received_distances));
+            }
+            SimulationMode::Aoa { angle_std, .. } | SimulationMode::Hybrid { angle_std, .. } => {
+                aoa = Some(self.noisy_bearings(rng, &received_pts, &tag_pt, angle_std));
+            }
+            SimulationMode::Range | SimulationMode::Rssi { .. } => {}
+        }
+
+        TestResult {
+            seed: self.seed,
+            iteration,
+            tag_pt,
+            anchor_pts,
+            real_distances: distances,
+            distance_coefficients,
+            adjusted_distances,
+            dropped,
+            tdoa,
+            aoa,
+            rssi,
+            predicted_pt: Point::new(f64::NAN, f64::NAN),
+            delta: f64::NAN,
+            uncertainty: None,
+            failure: None,
+        }
+    }
+
+    fn trial(&self, iteration: u64, rng: &mut R, tag_pt: Point) -> TestResult {
+        let mut result = self.measure(iteration, rng, tag_pt);
+        let (received_pts, received_distances) = result.received();
+
+        let fix = if received_pts.len() < self.mode.min_anchors() {
+            Err(SolveError::TooFewAnchors)
+        } else {
+            match (&self.mode, &result.tdoa, &result.aoa) {
+                (SimulationMode::Tdoa { callback, .. }, Some(tdoa), _) => {
+                    callback(&received_pts, tdoa)
+                }
+                (SimulationMode::Aoa { callback, .. }, _, Some(aoa)) => {
+                    callback(&received_pts, aoa)
+                }
+                (SimulationMode::Hybrid { callback, .. }, _, Some(aoa)) => {
+                    callback(&received_pts, &received_distances, aoa)
+                }
+                _ => (self.callback)(&received_pts, &received_distances),
+            }
+            .ok_or(SolveError::NoFix)
+        };
+
+        result.set_fix(fix);
+        result.uncertainty = self.result_uncertainty(&result);
+
+        result
+    }
+
+    /// Predicted accuracy of the fix of `result` under this runner's noise and `mode`, `None`
+    /// when the trial got no fix or the geometry is degenerate
+    pub fn result_uncertainty(&self, result: &TestResult) -> Option<Uncertainty> {
+        if result.failure.is_some() {
+            return None;
+        }
+
+        let (received_pts, _) = result.received();
+        let reference = result.tdoa.as_ref().map_or(0, |tdoa| tdoa.reference);
+
+        self.uncertainty(&received_pts, &result.predicted_pt, reference)
+    }
+
+    /// Runs every one of `localizers` on the same `times` trials, the anchors, tag and
+    /// measurements of which come from this runner's `mode`. Localizers have to get by with what
+    /// the mode measures: bearings are only taken in [`SimulationMode::Aoa`] and
+    /// [`SimulationMode::Hybrid`], and TDoA localizers difference the ranges outside of
+    /// [`SimulationMode::Tdoa`].
+    pub fn run_comparison(&self, times: i32, localizers: &[(&str, Localizer)]) -> Comparison {
+        let deltas = self
+            .run_parallel(times.max(0) as usize, |iteration| {
+                let mut rng = self.iteration_rng(iteration);
+                let tag_pt = self.rand_pt(&mut rng);
+                let result = self.measure(iteration, &mut rng, tag_pt);
+
+                localizers
+                    .iter()
+                    .map(
+                        |(_, localizer)| match localizer.solve_measurements(&result) {
+                            Ok(pt) => result.tag_pt.distance_to(&pt),
+                            Err(_) => f64::NAN,
+                        },
+                    )
+                    .collect::<Vec<_>>()
+            })
             .into_iter()
-            .map(|time| {
-                print!(
-                    "\rTest #{} / {}, ({:.2}%)",
-                    time,
-                    times,
-                    time as f64 / times as f64 * 100.0
-                );
-
-                let distances = anchor_pts
+            .flatten()
+            .collect();
+
+        Comparison::new(
+            localizers
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            deltas,
+        )
+    }
+
+    /// Tracks a tag following `path`, past anchors that stay put for the whole run.
+    ///
+    /// Every step the ranges get the runner's range noise and the ones that are not dropped are fed
+    /// both to `tracker` and, for comparison, to the runner's `callback`. The `mode` is ignored,
+    /// trackers consume ranges.
+    pub fn run_path(&self, tracker: &mut dyn Tracker, path: &[TimedPoint]) -> TrajectoryResult {
+        let mut rng = self.iteration_rng(0);
+        let rng = &mut rng;
+
+        let anchor_pts = self.placement.place(&self.bounds, self.anchor_count(), rng);
+
+        let steps = path
+            .iter()
+            .enumerate()
+            .map(|(step, TimedPoint { time, pt: tag_pt })| {
+                if step != 0 {
+                    tracker.predict(time - path[step - 1].time);
+                }
+
+                let adjusted_distances = anchor_pts
                     .iter()
-                    .map(|pt| pt.distance_to(&tag_pt))
+                    .map(|pt| {
+                        self.noisy_distance(rng, pt.distance_to(tag_pt))
+                            .unwrap_or(f64::NAN)
+                    })
                     .collect::<Vec<_>>();
-                let distance_coefficients = (0..self.num_of_anchors)
-                    .into_iter()
-                    .map(|_| self.rnd.gen_range(-self.error_margin..self.error_margin))
+
+                let received = (0..anchor_pts.len())
+                    .filter(|i| !adjusted_distances[*i].is_nan())
                     .collect::<Vec<_>>();
-                let adjusted_distances = distances
+                let received_pts = received
                     .iter()
-                    .enumerate()
-                    .map(|(index, dist)| dist + dist * distance_coefficients[index])
+                    .map(|i| anchor_pts[*i].clone())
+                    .collect::<Vec<_>>();
+                let received_distances = received
+                    .iter()
+                    .map(|i| adjusted_distances[*i])
+                    .collect::<Vec<_>>();
+                let variances = received_distances
+                    .iter()
+                    .map(|dist| self.range_variance(*dist))
                     .collect::<Vec<_>>();
 
-                let result = (self.callback)(&anchor_pts, &adjusted_distances);
+                tracker.update(&received_pts, &received_distances, &variances);
 
-                let delta = tag_pt.distance_to(&result);
+                let snapshot_pt = if received.len() < SimulationMode::Range.min_anchors() {
+                    Point::new(f64::NAN, f64::NAN)
+                } else {
+                    (self.callback)(&received_pts, &received_distances)
+                        .unwrap_or(Point::new(f64::NAN, f64::NAN))
+                };
+                let filtered_pt = tracker.position();
 
-                let result = TestResult {
+                TrackResult {
+                    time: *time,
+                    snapshot_delta: tag_pt.distance_to(&snapshot_pt),
+                    delta: tag_pt.distance_to(&filtered_pt),
                     tag_pt: tag_pt.clone(),
-                    anchor_pts: anchor_pts.clone(),
-                    real_distances: distances,
-                    distance_coefficients,
                     adjusted_distances,
-                    predicted_pt: result,
-                    delta,
-                };
+                    snapshot_pt,
+                    filtered_pt,
+                }
+            })
+            .collect::<Vec<_>>();
 
-                // println!("{}", result);
+        TrajectoryResult { anchor_pts, steps }
+    }
 
-                if time != times {
-                    tag_pt = self.rand_pt();
+    /// Generates `steps` positions `dt` seconds apart from `trajectory` within the bounds and
+    /// tracks them with [`TestRunner::run_path`]
+    pub fn run_trajectory(
+        &self,
+        tracker: &mut dyn Tracker,
+        trajectory: &Trajectory,
+        steps: usize,
+        dt: f64,
+    ) -> TrajectoryResult {
+        // Seeded apart from the anchors and noise of `run_path`
+        let path = trajectory.generate(&self.bounds, steps, dt, &mut R::seed_from_u64(self.seed));
 
-                    for pt in anchor_pts.iter_mut() {
-                        *pt = self.rand_pt();
-                    }
-                } else {
-                    println!()
-                }
+        self.run_path(tracker, &path)
+    }
+}
 
-                result
-            })
-            .collect::<Vec<_>>()
+#[cfg(test)]
+mod tests {
+    use crate::{multilateration::paper_way, progress::ProgressCallback};
+
+    use super::*;
+
+    #[test]
+    fn cancelling_stops_at_the_current_batch() {
+        let mut runner =
+            TestRunner::new(3, 0.05, Rectangle::new(0.0, 0.0, 100.0, 100.0), paper_way);
+        runner.progress = Box::new(ProgressCallback(|_, _| false));
+        runner.threads = 2;
+
+        let mut batches = 0;
+        let Ok(()) = runner.run_batched(
+            40,
+            10,
+            |iteration| iteration,
+            |_| {
+                batches += 1;
+
+                Ok::<(), Infallible>(())
+            },
+        );
+
+        assert_eq!(batches, 1);
     }
 }
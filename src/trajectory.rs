@@ -0,0 +1,325 @@
+use std::{f64::consts::PI, fmt::Display};
+
+use rand::Rng;
+use rand_distr::Normal;
+
+use crate::{point::Point, rectangle::Rectangle};
+
+/// Position of the tag at `time` seconds
+#[derive(PartialEq, Debug, Clone)]
+pub struct TimedPoint {
+    pub time: f64,
+    pub pt: Point,
+}
+
+impl TimedPoint {
+    pub fn new(time: f64, pt: Point) -> Self {
+        TimedPoint { time, pt }
+    }
+}
+
+impl Display for TimedPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "t={}: {}", self.time, self.pt)
+    }
+}
+
+/// Folds `value` back into `[min, max]` as if it bounced off the edges, also returning whether
+/// it ends up travelling the other way
+fn fold(value: f64, min: f64, max: f64) -> (f64, bool) {
+    let span = max - min;
+
+    if span <= 0.0 {
+        return (min, false);
+    }
+
+    let offset = (value - min).rem_euclid(2.0 * span);
+
+    if offset <= span {
+        (min + offset, false)
+    } else {
+        (min + 2.0 * span - offset, true)
+    }
+}
+
+/// Moves `pt` by `velocity * dt`, reflecting it and the velocity off the edges of `bounds`
+pub fn reflect(bounds: &Rectangle, pt: &Point, velocity: &mut Point, dt: f64) -> Point {
+    let next = pt + &(&*velocity * dt);
+    let (x, flip_x) = fold(next.x, bounds.pt.x, bounds.pt.x + bounds.width);
+    let (y, flip_y) = fold(next.y, bounds.pt.y, bounds.pt.y + bounds.height);
+
+    if flip_x {
+        velocity.x = -velocity.x;
+    }
+
+    if flip_y {
+        velocity.y = -velocity.y;
+    }
+
+    Point::new(x, y)
+}
+
+fn heading_velocity(speed: f64, heading: f64) -> Point {
+    let (sin, cos) = heading.sin_cos();
+
+    Point::new(speed * cos, speed * sin)
+}
+
+/// Tag motion models, every one of them kept inside the bounds by reflecting off the edges
+#[derive(PartialEq, Debug, Clone)]
+pub enum Trajectory {
+    /// Brownian motion, each axis moves by normally distributed steps with a standard deviation
+    /// of `step_std * sqrt(dt)`
+    RandomWalk { step_std: f64 },
+    /// Straight lines at `speed`, turning by up to `max_turn` radians either way at random times,
+    /// on average `turn_rate` times per second. `max_turn` must not be negative.
+    ConstantVelocity {
+        speed: f64,
+        turn_rate: f64,
+        max_turn: f64,
+    },
+    /// Heads for each of the `waypoints` in turn at `speed`, going back to the first one after the
+    /// last when `looped` and otherwise stopping there. A looped route must cover some distance.
+    Waypoints {
+        waypoints: Vec<Point>,
+        speed: f64,
+        looped: bool,
+    },
+    /// Lévy walk at `speed`. Flights go in uniformly random directions and their lengths follow
+    /// a Pareto distribution with exponent `alpha` starting at `min_flight`, so most are short but
+    /// a few cross most of the bounds. `min_flight` must be positive.
+    LevyFlight {
+        speed: f64,
+        alpha: f64,
+        min_flight: f64,
+    },
+}
+
+impl Trajectory {
+    /// Panics when `max_turn` is negative
+    pub fn constant_velocity(speed: f64, turn_rate: f64, max_turn: f64) -> Self {
+        let trajectory = Trajectory::ConstantVelocity {
+            speed,
+            turn_rate,
+            max_turn,
+        };
+        trajectory.validate();
+
+        trajectory
+    }
+
+    /// Panics unless `min_flight` is positive
+    pub fn levy_flight(speed: f64, alpha: f64, min_flight: f64) -> Self {
+        let trajectory = Trajectory::LevyFlight {
+            speed,
+            alpha,
+            min_flight,
+        };
+        trajectory.validate();
+
+        trajectory
+    }
+
+    /// Panics on settings that would make [`Trajectory::generate`] panic or never finish
+    fn validate(&self) {
+        match self {
+            Trajectory::ConstantVelocity { max_turn, .. } => {
+                assert!(*max_turn >= 0.0, "Bad maximum turn, must not be negative")
+            }
+            Trajectory::LevyFlight { min_flight, .. } => {
+                assert!(*min_flight > 0.0, "Bad minimum flight, must be positive")
+            }
+            Trajectory::Waypoints {
+                waypoints,
+                looped: true,
+                ..
+            } if waypoints.len() > 1 => {
+                let length = waypoints
+                    .iter()
+                    .zip(waypoints.iter().cycle().skip(1))
+                    .map(|(from, to)| from.distance_to(to))
+                    .sum::<f64>();
+
+                assert!(
+                    length > 0.0,
+                    "Bad waypoints, a looped route must have a length"
+                )
+            }
+            _ => {}
+        }
+    }
+
+    /// Samples `steps` positions `dt` seconds apart, starting at time 0. Panics on the settings
+    /// the constructors reject, a looped route of no length and a random walk with a `dt` that is
+    /// not positive.
+    pub fn generate(
+        &self,
+        bounds: &Rectangle,
+        steps: usize,
+        dt: f64,
+        rng: &mut impl Rng,
+    ) -> Vec<TimedPoint> {
+        self.validate();
+
+        if let Trajectory::RandomWalk { .. } = self {
+            assert!(dt > 0.0, "Bad time step, must be positive");
+        }
+
+        let mut pt = match self {
+            Trajectory::Waypoints { waypoints, .. } if !waypoints.is_empty() => {
+                reflect(bounds, &waypoints[0], &mut Point::new(0.0, 0.0), 0.0)
+            }
+            _ => Point::new(
+                rng.gen_range(bounds.x_range()),
+                rng.gen_range(bounds.y_range()),
+            ),
+        };
+        let mut velocity = Point::new(0.0, 0.0);
+        // Waypoint being headed for, or length left of the current Lévy flight
+        let mut target = 1;
+        let mut flight_left = 0.0;
+
+        if let Trajectory::ConstantVelocity { speed, .. } = self {
+            velocity = heading_velocity(*speed, rng.gen_range(-PI..PI));
+        }
+
+        (0..steps)
+            .map(|step| {
+                if step != 0 {
+                    pt = match self {
+                        Trajectory::RandomWalk { step_std } => {
+                            let noise = Normal::new(0.0, step_std * dt.sqrt())
+                                .expect("Bad random walk standard deviation");
+
+                            velocity = &Point::new(rng.sample(noise), rng.sample(noise)) / dt;
+
+                            reflect(bounds, &pt, &mut velocity, dt)
+                        }
+                        Trajectory::ConstantVelocity {
+                            turn_rate,
+                            max_turn,
+                            ..
+                        } => {
+                            // Poisson turn events, at most one per step
+                            if rng.gen_bool((1.0 - (-turn_rate * dt).exp()).clamp(0.0, 1.0)) {
+                                let (sin, cos) = rng.gen_range(-max_turn..=*max_turn).sin_cos();
+
+                                velocity = Point::new(
+                                    velocity.x * cos - velocity.y * sin,
+                                    velocity.x * sin + velocity.y * cos,
+                                );
+                            }
+
+                            reflect(bounds, &pt, &mut velocity, dt)
+                        }
+                        Trajectory::Waypoints {
+                            waypoints,
+                            speed,
+                            looped,
+                        } => {
+                            let mut travel = speed * dt;
+
+                            // Several close waypoints can be passed within one step
+                            while travel > 0.0 && target < waypoints.len() {
+                                let goal = &waypoints[target];
+                                let dist = pt.distance_to(goal);
+
+                                if dist > travel {
+                                    pt = &pt + &(&(goal - &pt) * (travel / dist));
+                                    break;
+                                }
+
+                                pt = goal.clone();
+                                travel -= dist;
+                                target += 1;
+
+                                if *looped && target == waypoints.len() && waypoints.len() > 1 {
+                                    target = 0;
+                                }
+                            }
+
+                            reflect(bounds, &pt, &mut velocity, 0.0)
+                        }
+                        Trajectory::LevyFlight {
+                            speed,
+                            alpha,
+                            min_flight,
+                        } => {
+                            let mut travel = speed * dt;
+
+                            while travel > 0.0 {
+                                if flight_left <= 0.0 {
+                                    let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+
+                                    flight_left = min_flight * u.powf(-1.0 / alpha);
+                                    velocity = heading_velocity(*speed, rng.gen_range(-PI..PI));
+                                }
+
+                                let leg = travel.min(flight_left);
+
+                                pt = reflect(bounds, &pt, &mut velocity, leg / speed);
+                                travel -= leg;
+                                flight_left -= leg;
+                            }
+
+                            pt.clone()
+                        }
+                    };
+                }
+
+                TimedPoint::new(step as f64 * dt, pt.clone())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn bounds() -> Rectangle {
+        Rectangle::new(0.0, 0.0, 100.0, 100.0)
+    }
+
+    #[test]
+    fn looped_waypoints_cycle_through_the_route() {
+        let trajectory = Trajectory::Waypoints {
+            waypoints: vec![Point::new(10.0, 10.0), Point::new(20.0, 10.0)],
+            speed: 10.0,
+            looped: true,
+        };
+        let path = trajectory.generate(&bounds(), 4, 1.0, &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(
+            path.iter().map(|step| step.pt.clone()).collect::<Vec<_>>(),
+            vec![
+                Point::new(10.0, 10.0),
+                Point::new(20.0, 10.0),
+                Point::new(10.0, 10.0),
+                Point::new(20.0, 10.0),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Bad waypoints")]
+    fn looped_route_without_length_is_rejected() {
+        let trajectory = Trajectory::Waypoints {
+            waypoints: vec![Point::new(10.0, 10.0), Point::new(10.0, 10.0)],
+            speed: 1.0,
+            looped: true,
+        };
+
+        trajectory.generate(&bounds(), 3, 1.0, &mut StdRng::seed_from_u64(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Bad time step")]
+    fn random_walk_without_time_step_is_rejected() {
+        let trajectory = Trajectory::RandomWalk { step_std: 1.0 };
+
+        trajectory.generate(&bounds(), 3, 0.0, &mut StdRng::seed_from_u64(0));
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    fmt::Display,
+    io::{self, BufRead, BufWriter, Lines, Write},
+    str::FromStr,
+};
+
+use crate::{
+    aoa::AoaMeasurement,
+    matrix::Matrix,
+    point::Point,
+    report::format_csv_value,
+    rssi::RssiMeasurement,
+    sink::TrialSink,
+    tdoa::TdoaMeasurement,
+    test_runner::TestResult,
+    uncertainty::{Dop, ErrorEllipse, Uncertainty},
+};
+
+/// Columns of every trial, ahead of the anchor columns. The uncertainty ones are empty when the
+/// result has none, `tdoa_reference` outside of TDoA runs and `failure` when the trial got a fix.
+const TRIAL_COLUMNS: [&str; 19] = [
+    "seed",
+    "iteration",
+    "tag_x",
+    "tag_y",
+    "predicted_x",
+    "predicted_y",
+    "delta",
+    "tdoa_reference",
+    "covariance_xx",
+    "covariance_xy",
+    "covariance_yx",
+    "covariance_yy",
+    "gdop",
+    "hdop",
+    "semi_major",
+    "semi_minor",
+    "orientation",
+    "confidence",
+    "failure",
+];
+
+/// Columns of every anchor, suffixed with its index. The measurement ones are empty when the run
+/// did not take that measurement or the anchor was dropped.
+const ANCHOR_COLUMNS: [&str; 9] = [
+    "anchor_x",
+    "anchor_y",
+    "real_distance",
+    "coefficient",
+    "adjusted_distance",
+    "dropped",
+    "range_difference",
+    "bearing",
+    "rssi",
+];
+
+/// Names of the columns of a file with `anchors` anchors per trial
+pub fn columns(anchors: usize) -> Vec<String> {
+    TRIAL_COLUMNS
+        .iter()
+        .map(|column| column.to_string())
+        .chain((0..anchors).flat_map(|i| {
+            ANCHOR_COLUMNS
+                .iter()
+                .map(move |column| format!("{}_{}", column, i))
+        }))
+        .collect()
+}
+
+/// Writes one CSV row per [`TestResult`] as it is handed over, so a run never has to be held in
+/// memory. Every row needs the same number of anchors.
+#[derive(Debug)]
+pub struct TrialWriter<W: Write> {
+    writer: BufWriter<W>,
+    anchors: usize,
+}
+
+impl<W: Write> TrialWriter<W> {
+    /// Starts the file with the header line for `anchors` anchors per trial
+    pub fn new(writer: W, anchors: usize) -> io::Result<Self> {
+        let mut writer = BufWriter::new(writer);
+        writeln!(writer, "{}", columns(anchors).join(","))?;
+
+        Ok(TrialWriter { writer, anchors })
+    }
+
+    pub fn write(&mut self, result: &TestResult) -> io::Result<()> {
+        if result.anchor_pts.len() != self.anchors {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Trial has {} anchors instead of {}",
+                    result.anchor_pts.len(),
+                    self.anchors
+                ),
+            ));
+        }
+
+        let uncertainty = result.uncertainty.as_ref();
+        let covariance = |row, col| uncertainty.and_then(|u| u.covariance.get(row, col).copied());
+
+        let mut cells = vec![
+            result.seed.to_string(),
+            result.iteration.to_string(),
+            result.tag_pt.x.to_string(),
+            result.tag_pt.y.to_string(),
+            result.predicted_pt.x.to_string(),
+            result.predicted_pt.y.to_string(),
+            result.delta.to_string(),
+            result
+                .tdoa
+                .as_ref()
+                .map_or(String::new(), |tdoa| tdoa.reference.to_string()),
+            format_csv_value(covariance(0, 0)),
+            format_csv_value(covariance(0, 1)),
+            format_csv_value(covariance(1, 0)),
+            format_csv_value(covariance(1, 1)),
+            format_csv_value(uncertainty.map(|u| u.dop.gdop)),
+            format_csv_value(uncertainty.map(|u| u.dop.hdop)),
+            format_csv_value(uncertainty.map(|u| u.ellipse.semi_major)),
+            format_csv_value(uncertainty.map(|u| u.ellipse.semi_minor)),
+            format_csv_value(uncertainty.map(|u| u.ellipse.orientation)),
+            format_csv_value(uncertainty.map(|u| u.ellipse.confidence)),
+            result
+                .failure
+                .map_or(String::new(), |failure| failure.name().to_string()),
+        ];
+
+        // TDoA and AoA measurements only cover the anchors that were not dropped
+        let mut received = 0;
+
+        for i in 0..self.anchors {
+            let dropped = result.dropped.contains(&i);
+            let measured = |values: Option<&Vec<f64>>| {
+                format_csv_value(
+                    values
+                        .filter(|_| !dropped)
+                        .and_then(|values| values.get(received).copied()),
+                )
+            };
+
+            cells.extend([
+                result.anchor_pts[i].x.to_string(),
+                result.anchor_pts[i].y.to_string(),
+                result.real_distances[i].to_string(),
+                result.distance_coefficients[i].to_string(),
+                result.adjusted_distances[i].to_string(),
+                (dropped as u8).to_string(),
+                measured(result.tdoa.as_ref().map(|tdoa| &tdoa.range_differences)),
+                measured(result.aoa.as_ref().map(|aoa| &aoa.bearings)),
+                format_csv_value(result.rssi.as_ref().map(|rssi| rssi.rssi[i])),
+            ]);
+
+            if !dropped {
+                received += 1;
+            }
+        }
+
+        writeln!(self.writer, "{}", cells.join(","))
+    }
+
+    /// Flushes the rows still buffered and hands back the underlying writer
+    pub fn finish(self) -> io::Result<W> {
+        self.writer.into_inner().map_err(|err| err.into_error())
+    }
+}
+
+impl<W: Write> TrialSink for TrialWriter<W> {
+    fn push(&mut self, result: &TestResult) -> io::Result<()> {
+        self.write(result)
+    }
+}
+
+#[derive(Debug)]
+pub enum TrialCsvError {
+    Io(io::Error),
+    /// The first line is not a header written by [`TrialWriter`]
+    BadHeader,
+    /// The row on `line`, counting the header as line 1, has the wrong number of cells
+    BadRow {
+        line: usize,
+    },
+    /// The cell in `column` of the row on `line` does not hold a valid value
+    BadValue {
+        line: usize,
+        column: String,
+    },
+}
+
+impl Display for TrialCsvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrialCsvError::Io(err) => write!(f, "{}", err),
+            TrialCsvError::BadHeader => write!(f, "Bad header"),
+            TrialCsvError::BadRow { line } => write!(f, "Bad number of cells on line {}", line),
+            TrialCsvError::BadValue { line, column } => {
+                write!(f, "Bad value in column {} on line {}", column, line)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for TrialCsvError {
+    fn from(err: io::Error) -> Self {
+        TrialCsvError::Io(err)
+    }
+}
+
+/// Reads back the rows of a [`TrialWriter`] one at a time
+#[derive(Debug)]
+pub struct TrialReader<R: BufRead> {
+    lines: Lines<R>,
+    columns: Vec<String>,
+    anchors: usize,
+    line: usize,
+}
+
+impl<R: BufRead> TrialReader<R> {
+    /// Reads the header, which sets the number of anchors every row has
+    pub fn new(reader: R) -> Result<Self, TrialCsvError> {
+        let mut lines = reader.lines();
+        let header = lines.next().ok_or(TrialCsvError::BadHeader)??;
+
+        let anchors = header
+            .split(',')
+            .count()
+            .saturating_sub(TRIAL_COLUMNS.len())
+            / ANCHOR_COLUMNS.len();
+        let columns = columns(anchors);
+
+        if header.trim_end() != columns.join(",") {
+            return Err(TrialCsvError::BadHeader);
+        }
+
+        Ok(TrialReader {
+            lines,
+            columns,
+            anchors,
+            line: 1,
+        })
+    }
+
+    pub fn anchors(&self) -> usize {
+        self.anchors
+    }
+
+    /// Value of the cell at `index`, `None` when it is empty
+    fn cell<T: FromStr>(&self, cells: &[&str], index: usize) -> Result<Option<T>, TrialCsvError> {
+        if cells[index].is_empty() {
+            return Ok(None);
+        }
+
+        cells[index]
+            .parse()
+            .map(Some)
+            .map_err(|_| TrialCsvError::BadValue {
+                line: self.line,
+                column: self.columns[index].clone(),
+            })
+    }
+
+    fn required<T: FromStr>(&self, cells: &[&str], index: usize) -> Result<T, TrialCsvError> {
+        self.cell(cells, index)?
+            .ok_or_else(|| TrialCsvError::BadValue {
+                line: self.line,
+                column: self.columns[index].clone(),
+            })
+    }
+
+    fn parse(&self, row: &str) -> Result<TestResult, TrialCsvError> {
+        let cells = row.trim_end().split(',').collect::<Vec<_>>();
+
+        if cells.len() != self.columns.len() {
+            return Err(TrialCsvError::BadRow { line: self.line });
+        }
+
+        let predicted_pt = Point::new(self.required(&cells, 4)?, self.required(&cells, 5)?);
+        let tdoa_reference = self.cell::<usize>(&cells, 7)?;
+
+        let uncertainty = match self.cell::<f64>(&cells, 8)? {
+            Some(xx) => Some(Uncertainty {
+                covariance: Matrix::from_iter(
+                    2,
+                    2,
+                    [
+                        xx,
+                        self.required(&cells, 9)?,
+                        self.required(&cells, 10)?,
+                        self.required(&cells, 11)?,
+                    ],
+                ),
+                dop: Dop {
+                    gdop: self.required(&cells, 12)?,
+                    hdop: self.required(&cells, 13)?,
+                },
+                ellipse: ErrorEllipse {
+                    center: predicted_pt.clone(),
+                    semi_major: self.required(&cells, 14)?,
+                    semi_minor: self.required(&cells, 15)?,
+                    orientation: self.required(&cells, 16)?,
+                    confidence: self.required(&cells, 17)?,
+                },
+            }),
+            None => None,
+        };
+
+        let mut result = TestResult {
+            seed: self.required(&cells, 0)?,
+            iteration: self.required(&cells, 1)?,
+            tag_pt: Point::new(self.required(&cells, 2)?, self.required(&cells, 3)?),
+            anchor_pts: vec![],
+            real_distances: vec![],
+            distance_coefficients: vec![],
+            adjusted_distances: vec![],
+            dropped: vec![],
+            tdoa: None,
+            aoa: None,
+            rssi: None,
+            predicted_pt,
+            delta: self.required(&cells, 6)?,
+            uncertainty,
+            failure: self.cell(&cells, 18)?,
+        };
+
+        let (mut range_differences, mut bearings, mut rssi) = (vec![], vec![], vec![]);
+
+        for i in 0..self.anchors {
+            let column = |offset: usize| TRIAL_COLUMNS.len() + i * ANCHOR_COLUMNS.len() + offset;
+
+            result.anchor_pts.push(Point::new(
+                self.required(&cells, column(0))?,
+                self.required(&cells, column(1))?,
+            ));
+            result
+                .real_distances
+                .push(self.required(&cells, column(2))?);
+            result
+                .distance_coefficients
+                .push(self.required(&cells, column(3))?);
+            result
+                .adjusted_distances
+                .push(self.required(&cells, column(4))?);
+
+            if self.required::<u8>(&cells, column(5))? != 0 {
+                result.dropped.push(i);
+            }
+
+            range_differences.extend(self.cell::<f64>(&cells, column(6))?);
+            bearings.extend(self.cell::<f64>(&cells, column(7))?);
+            rssi.extend(self.cell::<f64>(&cells, column(8))?);
+        }
+
+        result.tdoa =
+            tdoa_reference.map(|reference| TdoaMeasurement::new(reference, range_differences));
+        result.aoa = (!bearings.is_empty()).then(|| AoaMeasurement::new(bearings));
+        result.rssi =
+            (self.anchors > 0 && rssi.len() == self.anchors).then(|| RssiMeasurement::new(rssi));
+
+        Ok(result)
+    }
+}
+
+impl<R: BufRead> Iterator for TrialReader<R> {
+    type Item = Result<TestResult, TrialCsvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let row = self.lines.next()?;
+            self.line += 1;
+
+            match row {
+                Ok(row) if row.trim().is_empty() => continue,
+                Ok(row) => return Some(self.parse(&row)),
+                Err(err) => return Some(Err(err.into())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        localizer::{Localizer, SolveError},
+        multilateration::paper_way,
+        noise::{Dropout, Multiplicative, Stack},
+        rectangle::Rectangle,
+        rssi::PathLossModel,
+        test_runner::TestRunner,
+    };
+
+    use super::*;
+
+    /// Runner with 4 anchors, a third of the ranges of which get lost
+    fn runner(localizer: &str) -> TestRunner {
+        let mut runner =
+            TestRunner::new(4, 0.05, Rectangle::new(0.0, 0.0, 100.0, 100.0), paper_way);
+        runner.noise = Some(Box::new(Stack {
+            models: vec![
+                Box::new(Multiplicative::new(0.05)),
+                Box::new(Dropout::new(0.3)),
+            ],
+        }));
+        runner.seed = 7;
+
+        match localizer {
+            "rssi" => Localizer::from_name("paper_way")
+                .expect("Bad localizer name")
+                .configure(&mut runner, 0.05, 1.0, Some(PathLossModel::default())),
+            name => Localizer::from_name(name)
+                .expect("Bad localizer name")
+                .configure(&mut runner, 0.05, 1.0, None),
+        }
+
+        runner
+    }
+
+    fn write_all(results: &[TestResult]) -> String {
+        let mut writer = TrialWriter::new(Vec::new(), 4).expect("Bad write");
+
+        for result in results {
+            writer.write(result).expect("Bad write");
+        }
+
+        String::from_utf8(writer.finish().expect("Bad write")).expect("Bad UTF-8")
+    }
+
+    #[test]
+    fn every_measurement_kind_round_trips() {
+        for localizer in [
+            "paper_way",
+            "chan_taylor",
+            "bearing_way",
+            "hybrid_way",
+            "rssi",
+        ] {
+            let runner = runner(localizer);
+            let mut results = (0..20).map(|i| runner.run_iteration(i)).collect::<Vec<_>>();
+            results[0].set_fix(Err(SolveError::NoFix));
+
+            let csv = write_all(&results);
+            let read = TrialReader::new(csv.as_bytes())
+                .expect("Bad header")
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_or_else(|err| panic!("{}: {}", localizer, err));
+
+            assert_eq!(read.len(), results.len(), "{}", localizer);
+            assert_eq!(write_all(&read), csv, "{}", localizer);
+
+            for (original, read) in results.iter().zip(&read) {
+                assert_eq!(read.failure, original.failure, "{}", localizer);
+                assert_eq!(read.dropped, original.dropped, "{}", localizer);
+                assert_eq!(
+                    read.measurements(),
+                    original.measurements(),
+                    "{}",
+                    localizer
+                );
+                assert_eq!(read.tdoa, original.tdoa, "{}", localizer);
+                assert_eq!(read.aoa, original.aoa, "{}", localizer);
+                assert_eq!(read.rssi, original.rssi, "{}", localizer);
+            }
+
+            assert!(read[0].predicted_pt.x.is_nan() && read[0].delta.is_nan());
+            // RSSI readings come from the path loss model instead of the range noise
+            assert!(
+                localizer == "rssi" || read.iter().any(|result| !result.dropped.is_empty()),
+                "{}",
+                localizer
+            );
+        }
+    }
+}
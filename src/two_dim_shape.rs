@@ -19,4 +19,9 @@ pub trait TwoDimShape: Contains<Point> + Contains<Rectangle> + Contains<Circle>
     fn calc_min_span(&self) -> f64;
     /// Returns the length of the biggest line that can be drawn inside this object
     fn calc_max_span(&self) -> f64;
+
+    /// Returns the smallest axis aligned rectangle around the shape
+    fn calc_bounding_box(&self) -> Rectangle;
+    /// Returns an unstyled SVG element outlining the shape, in the shape's own coordinates
+    fn to_svg(&self) -> String;
 }
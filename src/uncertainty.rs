@@ -0,0 +1,383 @@
+use std::fmt::Display;
+
+use crate::{matrix::Matrix, point::Point, two_dim_shape::Contains};
+
+/// Confidence level used for the error ellipse carried by every [`Uncertainty`]
+pub const DEFAULT_CONFIDENCE: f64 = 0.95;
+
+/// Jacobian of the distances to every anchor with respect to `pt`, one unit vector per row
+pub fn range_jacobian(anchors: &[Point], pt: &Point) -> Matrix {
+    Matrix::from_iter(
+        anchors.len(),
+        2,
+        anchors.iter().flat_map(|anchor| {
+            let delta = pt - anchor;
+            let dist = delta.pow(2).sum().sqrt();
+
+            [delta.x / dist, delta.y / dist]
+        }),
+    )
+}
+
+/// Jacobian of the range differences to every anchor but the `reference` with respect to `pt`
+pub fn tdoa_jacobian(anchors: &[Point], reference: usize, pt: &Point) -> Matrix {
+    let ranges = range_jacobian(anchors, pt);
+    let reference_row = ranges
+        .get_row(reference)
+        .unwrap()
+        .copied()
+        .collect::<Vec<_>>();
+
+    Matrix::from_iter(
+        anchors.len() - 1,
+        2,
+        (0..anchors.len())
+            .filter(|i| *i != reference)
+            .flat_map(|i| {
+                ranges
+                    .get_row(i)
+                    .unwrap()
+                    .zip(reference_row.clone())
+                    .map(|(value, reference_value)| value - reference_value)
+                    .collect::<Vec<_>>()
+            }),
+    )
+}
+
+/// Jacobian of the bearings from every anchor with respect to `pt`
+pub fn bearing_jacobian(anchors: &[Point], pt: &Point) -> Matrix {
+    Matrix::from_iter(
+        anchors.len(),
+        2,
+        anchors.iter().flat_map(|anchor| {
+            let delta = pt - anchor;
+            let dist_squared = delta.pow(2).sum();
+
+            [-delta.y / dist_squared, delta.x / dist_squared]
+        }),
+    )
+}
+
+/// Stacks the rows of `top` above the rows of `bottom`
+pub fn stack(top: &Matrix, bottom: &Matrix) -> Option<Matrix> {
+    if top.n_cols != bottom.n_cols {
+        return None;
+    }
+
+    Some(Matrix::from_iter(
+        top.n_rows + bottom.n_rows,
+        top.n_cols,
+        top.data.iter().chain(bottom.data.iter()).copied(),
+    ))
+}
+
+/// Position covariance `(J^T R^-1 J)^-1` of a least squares estimate, for the measurement
+/// `jacobian` and measurement noise covariance `noise`. Use [`diagonal_covariance`] for
+/// independent measurements.
+pub fn covariance(jacobian: &Matrix, noise: &Matrix) -> Option<Matrix> {
+    if noise.data.iter().all(|v| *v == 0.0) {
+        return diagonal_covariance(jacobian, &vec![0.0; noise.n_rows]);
+    }
+
+    let jacobian_t = jacobian.transpose();
+
+    jacobian_t.dot(&noise.invert()?)?.dot(jacobian)?.invert()
+}
+
+/// [`covariance`] of independent measurements with `variances`, taking `R^-1` from their
+/// reciprocals.
+///
+/// Noise-free measurements pin the position down along their gradient. When they do so in every
+/// direction the covariance is zero and the error ellipse collapses to a point. `None` when the
+/// geometry leaves the position undetermined.
+pub fn diagonal_covariance(jacobian: &Matrix, variances: &[f64]) -> Option<Matrix> {
+    if jacobian.n_cols != 2 || jacobian.n_rows != variances.len() {
+        return None;
+    }
+
+    // Upper triangles of `J^T R^-1 J` over the noisy measurements and `J^T J` over the exact ones
+    let (mut noisy, mut exact) = ([0.0; 3], [0.0; 3]);
+
+    for (i, variance) in variances.iter().enumerate() {
+        let (x, y) = (*jacobian.get(i, 0)?, *jacobian.get(i, 1)?);
+        let (sums, weight) = if *variance == 0.0 {
+            (&mut exact, 1.0)
+        } else {
+            (&mut noisy, 1.0 / variance)
+        };
+
+        sums[0] += weight * x * x;
+        sums[1] += weight * x * y;
+        sums[2] += weight * y * y;
+    }
+
+    let [a, b, c] = exact;
+
+    if a + c == 0.0 {
+        return Matrix::from_iter(2, 2, [noisy[0], noisy[1], noisy[1], noisy[2]]).invert();
+    }
+
+    if a * c - b * b > f64::EPSILON * (a + c).powi(2) {
+        return Some(Matrix::new(2, 2));
+    }
+
+    // Only the direction across the exact measurements' gradient is left to the noisy ones
+    let (x, y) = if a >= c { (-b, a) } else { (-c, b) };
+    let norm = x.hypot(y);
+    let (x, y) = (x / norm, y / norm);
+    let information = noisy[0] * x * x + 2.0 * noisy[1] * x * y + noisy[2] * y * y;
+
+    if information <= 0.0 || !information.is_finite() {
+        return None;
+    }
+
+    Some(Matrix::from_iter(
+        2,
+        2,
+        [x * x, x * y, x * y, y * y].map(|v| v / information),
+    ))
+}
+
+/// Dilution of precision of the anchor geometry around `pt`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Dop {
+    /// Includes an unknown clock offset alongside the position, as in TDoA and GNSS
+    pub gdop: f64,
+    /// Position only
+    pub hdop: f64,
+}
+
+impl Dop {
+    /// Degenerate geometry has an infinite dilution of precision
+    pub fn new(anchors: &[Point], pt: &Point) -> Self {
+        let dop = |geometry: &Matrix| {
+            geometry
+                .transpose()
+                .dot(geometry)
+                .and_then(|m| m.invert())
+                .and_then(|m| m.trace())
+                .map(|trace| trace.sqrt())
+                .filter(|dop| dop.is_finite())
+                .unwrap_or(f64::INFINITY)
+        };
+
+        let position = range_jacobian(anchors, pt);
+        let with_clock = Matrix::from_iter(
+            anchors.len(),
+            3,
+            (0..anchors.len()).flat_map(|i| {
+                [
+                    *position.get(i, 0).unwrap(),
+                    *position.get(i, 1).unwrap(),
+                    1.0,
+                ]
+            }),
+        );
+
+        Dop {
+            gdop: if anchors.len() > 2 {
+                dop(&with_clock)
+            } else {
+                f64::INFINITY
+            },
+            hdop: dop(&position),
+        }
+    }
+}
+
+/// Ellipse containing the true position with probability `confidence`
+#[derive(PartialEq, Debug, Clone)]
+pub struct ErrorEllipse {
+    pub center: Point,
+    pub semi_major: f64,
+    pub semi_minor: f64,
+    /// Angle of the major axis in radians counter-clockwise from the x axis
+    pub orientation: f64,
+    pub confidence: f64,
+}
+
+impl ErrorEllipse {
+    /// Scales the eigen decomposition of a 2x2 `covariance` by the chi-squared quantile
+    pub fn from_covariance(center: Point, covariance: &Matrix, confidence: f64) -> Option<Self> {
+        if covariance.n_rows != 2 || !covariance.is_square() {
+            return None;
+        }
+
+        let a = *covariance.get(0, 0)?;
+        let b = *covariance.get(0, 1)?;
+        let c = *covariance.get(1, 1)?;
+
+        let mean = (a + c) / 2.0;
+        let radius = (((a - c) / 2.0).powi(2) + b.powi(2)).sqrt();
+        // Inverse of the chi-squared CDF with 2 degrees of freedom
+        let scale = (-2.0 * (1.0 - confidence).ln()).sqrt();
+
+        Some(ErrorEllipse {
+            center,
+            semi_major: scale * (mean + radius).max(0.0).sqrt(),
+            semi_minor: scale * (mean - radius).max(0.0).sqrt(),
+            orientation: 0.5 * (2.0 * b).atan2(a - c),
+            confidence,
+        })
+    }
+}
+
+impl Contains<Point> for ErrorEllipse {
+    fn contains(&self, obj: &Point) -> bool {
+        let delta = obj - &self.center;
+        let length = delta.x.hypot(delta.y);
+        let (sin, cos) = self.orientation.sin_cos();
+
+        // A collapsed axis only leaves the points along the other one, up to rounding, and a
+        // point ellipse only its center
+        let term = |offset: f64, semi_axis: f64| {
+            if offset.abs() <= 1e-9 * length {
+                0.0
+            } else {
+                (offset / semi_axis).powi(2)
+            }
+        };
+
+        term(delta.x * cos + delta.y * sin, self.semi_major)
+            + term(-delta.x * sin + delta.y * cos, self.semi_minor)
+            <= 1.0
+    }
+}
+
+impl Display for ErrorEllipse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}x{} @ {}rad ({}%)",
+            self.center,
+            self.semi_major,
+            self.semi_minor,
+            self.orientation,
+            self.confidence * 100.0
+        )
+    }
+}
+
+/// Predicted accuracy of a position estimate
+#[derive(PartialEq, Debug, Clone)]
+pub struct Uncertainty {
+    /// 2x2 position covariance
+    pub covariance: Matrix,
+    pub dop: Dop,
+    pub ellipse: ErrorEllipse,
+}
+
+impl Uncertainty {
+    pub fn new(anchors: &[Point], pt: &Point, covariance: Matrix) -> Option<Self> {
+        Some(Uncertainty {
+            ellipse: ErrorEllipse::from_covariance(pt.clone(), &covariance, DEFAULT_CONFIDENCE)?,
+            dop: Dop::new(anchors, pt),
+            covariance,
+        })
+    }
+
+    /// Squared Mahalanobis distance of `pt` from the estimate
+    pub fn mahalanobis_squared(&self, pt: &Point) -> Option<f64> {
+        let delta = pt - &self.ellipse.center;
+        let delta = Matrix::from_iter(2, 1, [delta.x, delta.y]);
+
+        delta
+            .transpose()
+            .dot(&self.covariance.invert()?)?
+            .dot(&delta)?
+            .get(0, 0)
+            .copied()
+    }
+
+    /// Root of the covariance trace, the expected distance between estimate and true position
+    pub fn drms(&self) -> f64 {
+        self.covariance.trace().unwrap().sqrt()
+    }
+}
+
+impl Display for Uncertainty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "DRMS {}, GDOP {}, HDOP {}, Ellipse {}",
+            self.drms(),
+            self.dop.gdop,
+            self.dop.hdop,
+            self.ellipse
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    /// Anchors 10 away from the origin along both axes
+    fn cross(n: usize) -> Vec<Point> {
+        [
+            Point::new(10.0, 0.0),
+            Point::new(0.0, 10.0),
+            Point::new(-10.0, 0.0),
+            Point::new(0.0, -10.0),
+        ][..n]
+            .to_vec()
+    }
+
+    #[test]
+    fn diagonal_covariance_weighs_by_reciprocal_variances() {
+        let origin = Point::new(0.0, 0.0);
+        let jacobian = range_jacobian(&cross(3), &origin);
+
+        // J^T R^-1 J = diag(1 + 1, 1 / 4)
+        let covariance =
+            diagonal_covariance(&jacobian, &[1.0, 4.0, 1.0]).expect("Bad missing covariance");
+
+        assert_eq!(covariance.data, vec![0.5, 0.0, 0.0, 4.0]);
+        assert_eq!(diagonal_covariance(&jacobian, &[1.0, 4.0]), None);
+    }
+
+    #[test]
+    fn symmetric_layout_dop() {
+        let dop = Dop::new(&cross(4), &Point::new(0.0, 0.0));
+
+        assert_close(dop.hdop, 1.0);
+        assert_close(dop.gdop, 1.25_f64.sqrt());
+        assert_eq!(
+            Dop::new(&cross(2), &Point::new(0.0, 0.0)).gdop,
+            f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn ellipse_contains_points_inside_its_rotated_axes() {
+        let ellipse = |semi_minor, orientation| ErrorEllipse {
+            center: Point::new(0.0, 0.0),
+            semi_major: 2.0,
+            semi_minor,
+            orientation,
+            confidence: DEFAULT_CONFIDENCE,
+        };
+
+        let upright = ellipse(1.0, PI / 2.0);
+        assert!(upright.contains(&Point::new(0.0, 1.9)));
+        assert!(upright.contains(&Point::new(0.9, 0.0)));
+        assert!(!upright.contains(&Point::new(1.5, 0.0)));
+
+        let collapsed = ellipse(0.0, PI / 4.0);
+        assert!(collapsed.contains(&Point::new(0.0, 0.0)));
+        assert!(collapsed.contains(&Point::new(1.0, 1.0)));
+        assert!(!collapsed.contains(&Point::new(0.1, -0.1)));
+        assert!(!collapsed.contains(&Point::new(2.0, 2.0)));
+    }
+}